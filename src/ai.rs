@@ -0,0 +1,179 @@
+use std::cmp::Reverse;
+
+use amethyst::ecs::Entity;
+use rand::{seq::SliceRandom, Rng};
+
+use crate::{
+    components::WorldPosition,
+    data::{Action, Behaviour, Direction},
+    resources::WorldMap,
+};
+
+// How much the stock behaviours want what they propose. The best proposal
+// that can be carried out wins.
+const FLEE_SCORE: i32 = 90;
+const RETURN_TO_POST_SCORE: i32 = 80;
+const KEEP_DISTANCE_SCORE: i32 = 75;
+const ATTACK_SCORE: i32 = 70;
+const HOLD_POST_SCORE: i32 = 55;
+const REGROUP_SCORE: i32 = 55;
+const CHASE_SCORE: i32 = 50;
+const IDLE_AT_POST_SCORE: i32 = 20;
+const WANDER_SCORE: i32 = 10;
+
+/// What a monster knows about its target.
+#[derive(Debug, Copy, Clone)]
+pub struct Target {
+    pub entity: Entity,
+    /// Where the target is when it is in sight, or else where it was last
+    /// seen or heard.
+    pub position: WorldPosition,
+    pub in_sight: bool,
+}
+
+/// What a monster knows when it decides what to do.
+pub struct Situation<'a> {
+    pub map: &'a WorldMap,
+    pub position: WorldPosition,
+    /// Remaining health in percent of the maximum.
+    pub health: i32,
+    pub target: Option<Target>,
+    /// Where the other monsters of its team on the level are.
+    pub allies: Vec<WorldPosition>,
+    pub home: WorldPosition,
+}
+
+/// What a behaviour wants to do. Walking somewhere is left to
+/// `MonsterAISystem`, which keeps track of paths.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Intent {
+    Approach(WorldPosition),
+    /// Step away from a position.
+    Retreat(WorldPosition),
+    Act(Action),
+}
+
+/// What the behaviours would like to do, best first. Earlier behaviours
+/// win ties.
+pub fn propose<R: Rng>(
+    behaviours: &[Behaviour],
+    situation: &Situation,
+    rng: &mut R,
+) -> Vec<Intent> {
+    let mut proposals: Vec<(i32, Intent)> = behaviours
+        .iter()
+        .filter_map(|behaviour| score(behaviour, situation, rng))
+        .collect();
+    proposals.sort_by_key(|(score, _)| Reverse(*score));
+    proposals.into_iter().map(|(_, intent)| intent).collect()
+}
+
+fn score<R: Rng>(
+    behaviour: &Behaviour,
+    situation: &Situation,
+    rng: &mut R,
+) -> Option<(i32, Intent)> {
+    let position = situation.position;
+    let target = situation.target;
+    match *behaviour {
+        Behaviour::Hunt => {
+            let target = target?;
+            if target.in_sight && position.is_adjacent(&target.position) {
+                Some((ATTACK_SCORE, Intent::Act(Action::Attack(target.entity))))
+            } else if position != target.position {
+                Some((CHASE_SCORE, Intent::Approach(target.position)))
+            } else {
+                // The trail ends here, what's left is searching around.
+                None
+            }
+        }
+        Behaviour::Wander => {
+            if target.map_or(false, |target| target.in_sight) {
+                return None;
+            }
+            let free: Vec<Direction> = Direction::ALL
+                .iter()
+                .cloned()
+                .filter(|dir| is_free(situation.map, &position.step_dir(*dir)))
+                .collect();
+            free.choose(rng)
+                .map(|dir| (WANDER_SCORE, Intent::Act(Action::Move(*dir))))
+        }
+        Behaviour::Flee(below) => {
+            let target = target?;
+            if situation.health <= below {
+                Some((FLEE_SCORE, Intent::Retreat(target.position)))
+            } else {
+                None
+            }
+        }
+        Behaviour::Ranged(range) => {
+            let target = target?;
+            let distance = position.distance(&target.position);
+            if target.in_sight && distance <= 1 {
+                Some((KEEP_DISTANCE_SCORE, Intent::Retreat(target.position)))
+            } else if target.in_sight && distance <= range {
+                Some((ATTACK_SCORE, Intent::Act(Action::Shoot(target.entity))))
+            } else if distance > 0 {
+                Some((CHASE_SCORE, Intent::Approach(target.position)))
+            } else {
+                None
+            }
+        }
+        Behaviour::Guard(radius) => {
+            let home = situation.home;
+            if position.distance(&home) > radius {
+                return Some((RETURN_TO_POST_SCORE, Intent::Approach(home)));
+            }
+            match target {
+                // Targets are only followed as far as the post reaches.
+                Some(target) if target.position.distance(&home) > radius => {
+                    if position == home {
+                        Some((HOLD_POST_SCORE, Intent::Act(Action::Wait)))
+                    } else {
+                        Some((HOLD_POST_SCORE, Intent::Approach(home)))
+                    }
+                }
+                None if position != home => Some((IDLE_AT_POST_SCORE, Intent::Approach(home))),
+                _ => None,
+            }
+        }
+        Behaviour::Pack(radius) => {
+            // Nobody takes on a target alone while the rest of the pack is
+            // elsewhere.
+            target?;
+            if situation
+                .allies
+                .iter()
+                .any(|ally| ally.distance(&position) <= radius)
+            {
+                return None;
+            }
+            situation
+                .allies
+                .iter()
+                .min_by_key(|ally| ally.distance(&position))
+                .map(|ally| (REGROUP_SCORE, Intent::Approach(*ally)))
+        }
+    }
+}
+
+/// The step from `from` that gets furthest away from `threat`, if any step
+/// gets further away at all.
+pub fn retreat(map: &WorldMap, from: &WorldPosition, threat: &WorldPosition) -> Option<Direction> {
+    let current = from.distance(threat);
+    Direction::ALL
+        .iter()
+        .cloned()
+        .filter(|dir| is_free(map, &from.step_dir(*dir)))
+        .map(|dir| (from.step_dir(dir).distance(threat), dir))
+        .filter(|(distance, _)| *distance > current)
+        .max_by_key(|(distance, _)| *distance)
+        .map(|(_, dir)| dir)
+}
+
+fn is_free(map: &WorldMap, pos: &WorldPosition) -> bool {
+    map.get(pos).map_or(false, |tile| {
+        tile.terrain.is_passable() && tile.character.is_none()
+    })
+}
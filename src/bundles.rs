@@ -1,26 +1,43 @@
-use crate::{systems::*, tui::TuiBundle};
+use crate::{
+    systems::*,
+    tui::{SharedBackend, TuiBundle},
+};
 use amethyst::{
     core::{bundle::SystemBundle, Error},
     ecs::prelude::DispatcherBuilder,
 };
 
-#[derive(Default)]
-pub struct LiveBundle;
+pub struct LiveBundle {
+    backend: SharedBackend,
+}
+
+impl LiveBundle {
+    pub fn new(backend: SharedBackend) -> Self {
+        LiveBundle { backend }
+    }
+}
 
 impl<'a, 'b> SystemBundle<'a, 'b> for LiveBundle {
     fn build(self, builder: &mut DispatcherBuilder<'a, 'b>) -> Result<(), Error> {
-        TuiBundle::new().build(builder)?;
+        TuiBundle::new(self.backend).build(builder)?;
         builder.add(
             DetectPlayerActionSystem::default(),
             "detect_player_action",
             &[],
         );
+        builder.add(
+            RecordInputSystem::default(),
+            "record_input",
+            &["detect_player_action"],
+        );
         builder.add(LogDisplaySystem::default(), "log_display", &[]);
         builder.add(InventoryDisplaySystem::default(), "inventory_display", &[]);
+        builder.add(CameraSystem, "camera", &[]);
+        builder.add(BoardDisplaySystem::default(), "board_display", &["camera"]);
         builder.add(
             ApplyBoardPositionSystem::default(),
             "apply_board_position",
-            &[],
+            &["camera"],
         );
         Ok(())
     }
@@ -31,23 +48,21 @@ pub struct TickBundle;
 
 impl<'a, 'b> SystemBundle<'a, 'b> for TickBundle {
     fn build(self, builder: &mut DispatcherBuilder<'a, 'b>) -> Result<(), Error> {
-        builder.add(PlayerMovementSystem, "player_movement", &[]);
-        builder.add(ApplyAttacksSystem, "apply_attacks", &["player_movement"]);
-        builder.add(ApplyMovementSystem, "apply_movement", &["player_movement"]);
+        builder.add(SchedulerSystem, "scheduler", &[]);
+        builder.add(PlayerControlSystem, "player_control", &["scheduler"]);
+        builder.add(MonsterAISystem::default(), "monster_ai", &["scheduler"]);
         builder.add(
-            ApplyWorldMapSystem::default(),
-            "apply_worldmap",
-            &["apply_attacks", "apply_movement"],
+            ResolveActionsSystem,
+            "resolve_actions",
+            &["player_control", "monster_ai"],
         );
-        builder.add(AggressiveAISystem, "ai_movement", &["apply_worldmap"]);
-        builder.add(ApplyAttacksSystem, "apply_attacks_2", &["ai_movement"]);
-        builder.add(ApplyMovementSystem, "apply_movement_2", &["ai_movement"]);
         builder.add(
             ApplyWorldMapSystem::default(),
-            "apply_worldmap_2",
-            &["apply_attacks_2", "apply_movement_2"],
+            "apply_worldmap",
+            &["resolve_actions"],
         );
-        builder.add(StunSystem, "stun_system", &["apply_attacks_2"]);
+        builder.add(StunSystem, "stun_system", &["resolve_actions"]);
+        builder.add(FieldOfViewSystem, "field_of_view", &["apply_worldmap"]);
         Ok(())
     }
 }
@@ -1,6 +1,9 @@
 use amethyst::ecs::{Component, DenseVecStorage, Entity, FlaggedStorage, NullStorage};
 
-use crate::data::{Direction, ItemPart, ItemProperties};
+use crate::data::{
+    Action, ActionError, Behaviour, Direction, EquipSlot, ItemKind, ItemPart, ItemProperties,
+    StatusEffect,
+};
 
 pub use amethyst::core::Named;
 
@@ -18,7 +21,7 @@ impl Component for Character {
     type Storage = NullStorage<Self>;
 }
 
-#[derive(Debug, Copy, Clone, Default)]
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub struct WorldPosition {
     pub x: i32,
     pub y: i32,
@@ -34,45 +37,65 @@ impl WorldPosition {
     }
 
     pub fn step_dir(&self, dir: Direction) -> WorldPosition {
-        let mut wp = self.clone();
-        match dir {
-            Direction::Up => wp.y -= 1,
-            Direction::Down => wp.y += 1,
-            Direction::Left => wp.x -= 1,
-            Direction::Right => wp.x += 1,
-        }
-        return wp;
+        let (dx, dy) = dir.delta();
+        WorldPosition::new(self.x + dx, self.y + dy)
+    }
+
+    pub fn direction_to(&self, other: &WorldPosition) -> Option<Direction> {
+        Direction::from_delta(other.x - self.x, other.y - self.y)
+    }
+
+    /// Number of steps between two positions when diagonal steps are allowed.
+    pub fn distance(&self, other: &WorldPosition) -> i32 {
+        (self.x - other.x).abs().max((self.y - other.y).abs())
+    }
+
+    /// Melee range: the eight surrounding tiles.
+    pub fn is_adjacent(&self, other: &WorldPosition) -> bool {
+        self.distance(other) == 1
     }
 }
 
-#[derive(Debug, Copy, Clone)]
+#[derive(Debug, Copy, Clone, Serialize, Deserialize)]
 pub struct Team(pub u32);
 
 impl Component for Team {
     type Storage = DenseVecStorage<Self>;
 }
 
-#[derive(Debug, Clone)]
-pub struct AggressiveAI {
-    pub aggressive_against: Vec<u32>,
+/// Makes a monster act on its own, see `MonsterAISystem`. Who it goes
+/// after depends on how its `Team` gets along with the others, see
+/// `resources::Factions`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Ai {
+    /// Every turn the one that scores best is followed.
+    pub behaviours: Vec<Behaviour>,
+    /// Where the monster was spawned, used by `Behaviour::Guard`.
+    pub home: WorldPosition,
 }
 
-impl Component for AggressiveAI {
+impl Component for Ai {
     type Storage = DenseVecStorage<Self>;
 }
 
-impl AggressiveAI {
-    pub fn new<'a, T>(against: T) -> Self
-    where
-        T: IntoIterator<Item = &'a u32>,
-    {
-        AggressiveAI {
-            aggressive_against: against.into_iter().cloned().collect(),
-        }
+impl Ai {
+    pub fn new(behaviours: Vec<Behaviour>, home: WorldPosition) -> Self {
+        Ai { behaviours, home }
+    }
+
+    /// How far the monster shoots, if it does, see `Behaviour::Ranged`.
+    pub fn range(&self) -> Option<i32> {
+        self.behaviours
+            .iter()
+            .filter_map(|behaviour| match behaviour {
+                Behaviour::Ranged(range) => Some(*range),
+                _ => None,
+            })
+            .max()
     }
 }
 
-#[derive(Default)]
+#[derive(Default, Debug, Clone, Serialize, Deserialize)]
 pub struct Stunned {
     pub time: u32,
 }
@@ -88,8 +111,68 @@ impl Stunned {
     }
 }
 
+/// Speed of a normal character. Speeds are relative to it, so a speed of
+/// 20 acts twice as often and one of 5 half as often.
+pub const NORMAL_SPEED: i32 = 10;
+/// Energy an actor needs before it can take an action, and what a full
+/// action costs.
+pub const ACTION_ENERGY: i32 = 100;
+
+/// Something that takes turns. Gains `speed` energy every tick of the
+/// `Scheduler` and acts once it has `ACTION_ENERGY`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Actor {
+    pub speed: i32,
+    pub energy: i32,
+}
+
+impl Component for Actor {
+    type Storage = DenseVecStorage<Self>;
+}
+
+impl Actor {
+    pub fn new(speed: i32) -> Self {
+        Actor { speed, energy: 0 }
+    }
+
+    pub fn is_ready(&self) -> bool {
+        self.energy >= ACTION_ENERGY
+    }
+
+    /// Ticks until the actor is ready, `None` if it never will be.
+    pub fn ticks_until_ready(&self) -> Option<u64> {
+        if self.is_ready() {
+            Some(0)
+        } else if self.speed <= 0 {
+            None
+        } else {
+            let missing = ACTION_ENERGY - self.energy;
+            Some(((missing + self.speed - 1) / self.speed) as u64)
+        }
+    }
+}
+
+/// The outcome of the last action the character submitted, so that
+/// whatever controls it can react to failures.
+#[derive(Debug, Clone)]
+pub struct LastAction {
+    pub action: Action,
+    pub result: Result<(), ActionError>,
+}
+
+impl Component for LastAction {
+    type Storage = DenseVecStorage<Self>;
+}
+
+/// Turns a monster keeps looking for a target it lost track of.
+pub const SEARCH_TURNS: u32 = 10;
+
 pub struct AggressionTarget {
     pub target: Entity,
+    /// Where the target was last seen or heard.
+    pub last_known: WorldPosition,
+    /// Turns left before the monster gives up on a target it can't see.
+    pub search: u32,
 }
 
 impl Component for AggressionTarget {
@@ -97,13 +180,38 @@ impl Component for AggressionTarget {
 }
 
 impl AggressionTarget {
-    pub fn new(target: Entity) -> Self {
-        AggressionTarget { target }
+    pub fn new(target: Entity, last_known: WorldPosition) -> Self {
+        AggressionTarget {
+            target,
+            last_known,
+            search: SEARCH_TURNS,
+        }
+    }
+}
+
+/// A route an AI is following, cached between ticks.
+/// `steps` is stored in reverse, so the next step is last.
+#[derive(Debug, Clone)]
+pub struct CachedPath {
+    pub goal: WorldPosition,
+    pub steps: Vec<WorldPosition>,
+}
+
+impl Component for CachedPath {
+    type Storage = DenseVecStorage<Self>;
+}
+
+impl CachedPath {
+    pub fn new(goal: WorldPosition, mut path: Vec<WorldPosition>) -> Self {
+        path.reverse();
+        CachedPath { goal, steps: path }
     }
 }
 
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Health {
     pub health: i32,
+    pub max: i32,
 }
 
 impl Component for Health {
@@ -112,7 +220,17 @@ impl Component for Health {
 
 impl Health {
     pub fn new(health: i32) -> Self {
-        Health { health }
+        Health {
+            health,
+            max: health,
+        }
+    }
+
+    /// Returns how much health was actually restored.
+    pub fn heal(&mut self, amount: i32) -> i32 {
+        let healed = amount.min(self.max - self.health).max(0);
+        self.health += healed;
+        healed
     }
 }
 
@@ -144,9 +262,44 @@ pub enum InventoryDisplayKind {
     Ground,
 }
 
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum InventorySort {
+    /// The order the items were picked up in.
+    Carried,
+    Name,
+    /// Highest damage first.
+    Damage,
+    Kind,
+}
+
+impl InventorySort {
+    pub fn next(self) -> Self {
+        use InventorySort::*;
+        match self {
+            Carried => Name,
+            Name => Damage,
+            Damage => Kind,
+            Kind => Carried,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        use InventorySort::*;
+        match self {
+            Carried => "pickup",
+            Name => "name",
+            Damage => "damage",
+            Kind => "type",
+        }
+    }
+}
+
 pub struct InventoryDisplay {
     pub display_kind: InventoryDisplayKind,
     pub cursor_pos: Option<i32>,
+    /// Sorting and filtering only apply to carried items.
+    pub sort: InventorySort,
+    pub filter: Option<ItemKind>,
 }
 
 impl InventoryDisplay {
@@ -154,8 +307,46 @@ impl InventoryDisplay {
         InventoryDisplay {
             display_kind,
             cursor_pos: None,
+            sort: InventorySort::Carried,
+            filter: None,
         }
     }
+
+    /// Cycles through showing everything and each `ItemKind`.
+    pub fn next_filter(&mut self) {
+        self.filter = match self.filter {
+            None => Some(ItemKind::ALL[0]),
+            Some(kind) => ItemKind::ALL
+                .iter()
+                .position(|x| *x == kind)
+                .and_then(|i| ItemKind::ALL.get(i + 1))
+                .cloned(),
+        };
+    }
+
+    /// Indices of the carried items that pass the filter, in display order.
+    pub fn carried_rows(&self, items: &[Item]) -> Vec<usize> {
+        let props: Vec<_> = items.iter().map(|x| x.properties()).collect();
+        let mut rows: Vec<usize> = (0..items.len())
+            .filter(|&i| self.filter.map_or(true, |kind| props[i].kind() == kind))
+            .collect();
+        match self.sort {
+            InventorySort::Carried => {}
+            InventorySort::Name => rows.sort_by_key(|&i| props[i].full_name()),
+            InventorySort::Damage => rows.sort_by_key(|&i| -props[i].damage.unwrap_or(0)),
+            InventorySort::Kind => rows.sort_by_key(|&i| (props[i].kind(), props[i].full_name())),
+        }
+        rows
+    }
+
+    /// Shown under the title of the own inventory.
+    pub fn modes_description(&self) -> String {
+        format!(
+            "Sorted by {}, showing {}",
+            self.sort.name(),
+            self.filter.map_or("everything", |x| x.name())
+        )
+    }
 }
 
 impl Component for InventoryDisplay {
@@ -169,6 +360,9 @@ impl Component for BoardDisplay {
     type Storage = NullStorage<Self>;
 }
 
+/// Total weight a character can carry, worn items included.
+pub const CARRY_CAPACITY: i32 = 20;
+
 #[derive(Default)]
 pub struct Inventory {
     pub items: Vec<Item>,
@@ -176,7 +370,37 @@ pub struct Inventory {
 
 impl Inventory {
     pub fn new(items: Vec<Item>) -> Self {
-        Inventory { items }
+        let mut inventory = Inventory::default();
+        for item in items {
+            inventory.add(item);
+        }
+        inventory
+    }
+
+    /// Puts `item` on a matching stack, if there is one.
+    pub fn add(&mut self, item: Item) {
+        match self.items.iter_mut().find(|x| x.stacks_with(&item)) {
+            Some(stack) => stack.count += item.count,
+            None => self.items.push(item),
+        }
+    }
+
+    /// Takes a single item off the stack at `index`.
+    pub fn take(&mut self, index: usize) -> Item {
+        let stack = &mut self.items[index];
+        if stack.count > 1 {
+            stack.count -= 1;
+            Item {
+                count: 1,
+                ..stack.clone()
+            }
+        } else {
+            self.items.remove(index)
+        }
+    }
+
+    pub fn weight(&self) -> i32 {
+        self.items.iter().map(|x| x.weight()).sum()
     }
 }
 
@@ -184,9 +408,16 @@ impl Component for Inventory {
     type Storage = FlaggedStorage<Self, DenseVecStorage<Self>>;
 }
 
-#[derive(Debug, Clone, PartialEq, Hash)]
+#[derive(Debug, Clone, PartialEq, Hash, Serialize, Deserialize)]
 pub struct Item {
     pub parts: Vec<ItemPart>,
+    /// Size of the stack. Only stackable items go above 1.
+    #[serde(default = "default_item_count")]
+    pub count: u32,
+}
+
+fn default_item_count() -> u32 {
+    1
 }
 
 impl Item {
@@ -196,18 +427,76 @@ impl Item {
     {
         Item {
             parts: parts.into_iter().collect(),
+            count: 1,
         }
     }
 
-    pub fn description(&self) -> String {
+    pub fn stacks_with(&self, other: &Item) -> bool {
+        self.parts == other.parts && self.properties().is_stackable()
+    }
+
+    /// Weight of the whole stack.
+    pub fn weight(&self) -> i32 {
+        self.properties().weight * self.count as i32
+    }
+
+    pub fn properties(&self) -> ItemProperties {
         let mut props = ItemProperties::default();
         for part in &self.parts {
             part.collect_properties(&mut props);
         }
-        let mut desc = String::new();
-        desc.push_str(&props.name);
+        props
+    }
+
+    /// Spends one charge. Returns true if the item is used up, which is
+    /// always the case for items without charges.
+    pub fn use_charge(&mut self) -> bool {
+        let mut remaining = 0;
+        let mut spent = false;
+        for part in &mut self.parts {
+            if let ItemPart::Charges(charges) = part {
+                if !spent && *charges > 0 {
+                    *charges -= 1;
+                    spent = true;
+                }
+                remaining += *charges;
+            }
+        }
+        remaining == 0
+    }
+
+    /// The full name followed by everything that sets the item apart,
+    /// e.g. "Rusty dagger (ATK 3, stuns 1, wt 2, $5)".
+    pub fn description(&self) -> String {
+        let props = self.properties();
+        let mut stats = Vec::new();
         if let Some(dmg) = props.damage {
-            desc.push_str(&format!(" (ATK {})", dmg));
+            stats.push(format!("ATK {}", dmg));
+        }
+        if let Some(def) = props.defense {
+            stats.push(format!("DEF {}", def));
+        }
+        if let Some(heal) = props.heal {
+            stats.push(format!("heals {}", heal));
+        }
+        stats.extend(props.on_hit.iter().map(|effect| effect.description()));
+        stats.extend(props.on_use.iter().map(|effect| effect.description()));
+        if let Some(charges) = props.charges {
+            stats.push(format!("{} charges", charges));
+        }
+        if props.weight != 0 {
+            stats.push(format!("wt {}", props.weight));
+        }
+        if props.value != 0 {
+            stats.push(format!("${}", props.value));
+        }
+
+        let mut desc = props.full_name();
+        if self.count > 1 {
+            desc.push_str(&format!(" x{}", self.count));
+        }
+        if !stats.is_empty() {
+            desc.push_str(&format!(" ({})", stats.join(", ")));
         }
         desc
     }
@@ -217,9 +506,101 @@ impl Component for Item {
     type Storage = DenseVecStorage<Self>;
 }
 
+/// Worn items, kept sorted by slot. They are not part of the `Inventory`.
+#[derive(Default, Debug, Clone)]
+pub struct Equipment {
+    pub items: Vec<(EquipSlot, Item)>,
+}
+
+impl Equipment {
+    pub fn new(items: Vec<(EquipSlot, Item)>) -> Self {
+        Equipment { items }
+    }
+
+    /// Puts on `item`, returning whatever had to be taken off to make room.
+    pub fn equip(&mut self, slot: EquipSlot, item: Item) -> Option<Item> {
+        let mut removed = None;
+        if self.items.iter().filter(|(s, _)| *s == slot).count() >= slot.capacity() {
+            let index = self.items.iter().position(|(s, _)| *s == slot).unwrap();
+            removed = Some(self.items.remove(index).1);
+        }
+        self.items.push((slot, item));
+        self.items.sort_by_key(|(slot, _)| *slot);
+        removed
+    }
+
+    pub fn unequip(&mut self, index: usize) -> Option<Item> {
+        if index < self.items.len() {
+            Some(self.items.remove(index).1)
+        } else {
+            None
+        }
+    }
+
+    pub fn damage(&self) -> i32 {
+        self.items
+            .iter()
+            .filter_map(|(_, item)| item.properties().damage)
+            .sum()
+    }
+
+    pub fn on_hit(&self) -> Vec<StatusEffect> {
+        self.items
+            .iter()
+            .flat_map(|(_, item)| item.properties().on_hit)
+            .collect()
+    }
+
+    pub fn weight(&self) -> i32 {
+        self.items.iter().map(|(_, item)| item.weight()).sum()
+    }
+
+    pub fn defense(&self) -> i32 {
+        self.items
+            .iter()
+            .filter_map(|(_, item)| item.properties().defense)
+            .sum()
+    }
+}
+
+impl Component for Equipment {
+    type Storage = FlaggedStorage<Self, DenseVecStorage<Self>>;
+}
+
 #[derive(Debug, Copy, Clone, PartialEq, Default)]
 pub struct Controlled;
 
 impl Component for Controlled {
     type Storage = NullStorage<Self>;
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ticks_until_ready_rounds_up() {
+        let actor = |speed, energy| Actor { speed, energy };
+        assert_eq!(actor(20, 0).ticks_until_ready(), Some(5));
+        assert_eq!(actor(10, 0).ticks_until_ready(), Some(10));
+        assert_eq!(actor(5, 0).ticks_until_ready(), Some(20));
+        assert_eq!(actor(30, 0).ticks_until_ready(), Some(4));
+        assert_eq!(actor(10, 95).ticks_until_ready(), Some(1));
+    }
+
+    #[test]
+    fn ready_actors_need_no_ticks() {
+        let actor = Actor {
+            speed: 10,
+            energy: ACTION_ENERGY,
+        };
+        assert!(actor.is_ready());
+        assert_eq!(actor.ticks_until_ready(), Some(0));
+    }
+
+    #[test]
+    fn actors_without_speed_never_get_ready() {
+        assert_eq!(Actor::new(0).ticks_until_ready(), None);
+        assert_eq!(Actor::new(-5).ticks_until_ready(), None);
+    }
+}
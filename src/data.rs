@@ -1,42 +1,473 @@
+use crate::components::{ACTION_ENERGY, CARRY_CAPACITY};
 use amethyst::ecs::prelude::*;
 use std::{
     borrow::Cow,
     collections::hash_map::DefaultHasher,
+    fmt,
     hash::{Hash, Hasher},
 };
 
-#[derive(Debug, Copy, Clone, PartialEq)]
+#[derive(Debug, Copy, Clone, PartialEq, Serialize, Deserialize)]
 pub enum Direction {
     Up,
     Down,
     Left,
     Right,
+    UpLeft,
+    UpRight,
+    DownLeft,
+    DownRight,
 }
 
-#[derive(Debug, Copy, Clone, PartialEq)]
+impl Direction {
+    pub const ALL: [Direction; 8] = [
+        Direction::Up,
+        Direction::Down,
+        Direction::Left,
+        Direction::Right,
+        Direction::UpLeft,
+        Direction::UpRight,
+        Direction::DownLeft,
+        Direction::DownRight,
+    ];
+
+    pub fn delta(self) -> (i32, i32) {
+        use Direction::*;
+        match self {
+            Up => (0, -1),
+            Down => (0, 1),
+            Left => (-1, 0),
+            Right => (1, 0),
+            UpLeft => (-1, -1),
+            UpRight => (1, -1),
+            DownLeft => (-1, 1),
+            DownRight => (1, 1),
+        }
+    }
+
+    pub fn from_delta(dx: i32, dy: i32) -> Option<Direction> {
+        Direction::ALL
+            .iter()
+            .cloned()
+            .find(|dir| dir.delta() == (dx, dy))
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Hash, Serialize, Deserialize)]
+pub enum Terrain {
+    Floor,
+    Wall,
+    ClosedDoor,
+    OpenDoor,
+    Water,
+    Rubble,
+    StairsDown,
+    StairsUp,
+}
+
+impl Default for Terrain {
+    fn default() -> Self {
+        Terrain::Floor
+    }
+}
+
+impl Terrain {
+    pub fn name(self) -> &'static str {
+        use Terrain::*;
+        match self {
+            Floor => "Floor",
+            Wall => "Wall",
+            ClosedDoor => "Closed door",
+            OpenDoor => "Open door",
+            Water => "Water",
+            Rubble => "Rubble",
+            StairsDown => "Stairs down",
+            StairsUp => "Stairs up",
+        }
+    }
+
+    pub fn glyph(self) -> char {
+        use Terrain::*;
+        match self {
+            Floor => '.',
+            Wall => '#',
+            ClosedDoor => '+',
+            OpenDoor => '\'',
+            Water => '~',
+            Rubble => ':',
+            StairsDown => '>',
+            StairsUp => '<',
+        }
+    }
+
+    /// Can a character stand on this tile.
+    /// Closed doors aren't passable, but walking into one opens it.
+    pub fn is_passable(self) -> bool {
+        use Terrain::*;
+        match self {
+            Floor | OpenDoor | Rubble | StairsDown | StairsUp => true,
+            Wall | ClosedDoor | Water => false,
+        }
+    }
+
+    /// Does this tile block line of sight.
+    pub fn is_opaque(self) -> bool {
+        use Terrain::*;
+        match self {
+            Wall | ClosedDoor => true,
+            Floor | OpenDoor | Water | Rubble | StairsDown | StairsUp => false,
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Hash, Serialize, Deserialize)]
+pub enum TileVisibility {
+    Unseen,
+    Remembered,
+    Visible,
+}
+
+impl Default for TileVisibility {
+    fn default() -> Self {
+        TileVisibility::Unseen
+    }
+}
+
+/// Which way a flight of stairs leads.
+#[derive(Debug, Copy, Clone, PartialEq, Serialize, Deserialize)]
+pub enum Stairs {
+    Up,
+    Down,
+}
+
+impl Stairs {
+    pub fn terrain(self) -> Terrain {
+        match self {
+            Stairs::Up => Terrain::StairsUp,
+            Stairs::Down => Terrain::StairsDown,
+        }
+    }
+}
+
+impl fmt::Display for Stairs {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Stairs::Up => write!(f, "up"),
+            Stairs::Down => write!(f, "down"),
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Serialize, Deserialize)]
 pub enum PlayerAction {
     Move(Direction),
     Wait,
     Grab,
+    /// Picks up the item at this index of the tile the player is on.
+    PickUp(usize),
+    /// Goes up or down the stairs the player is standing on.
+    TakeStairs(Stairs),
+    /// Uses the carried item at this index.
+    Use(usize),
+    /// Puts the carried item at this index on the ground.
+    Drop(usize),
+    /// Throws the carried item at this index.
+    Throw(usize, Direction),
+    /// Attacks whoever is in that direction, even if they aren't hostile.
+    Attack(Direction),
     Quit,
 }
 
-#[derive(Debug, Copy, Clone, PartialEq)]
-pub struct Attack {
-    pub attacker: Entity,
-    pub target: Entity,
+/// Something a character does with its turn, whether it is controlled by
+/// the player or by an AI. Carried out by `ResolveActionsSystem`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Action {
+    /// Steps in a direction. Stepping into a character of a hostile team
+    /// attacks it, the player swaps places with anybody else. Stepping
+    /// into a closed door opens it.
+    Move(Direction),
+    /// Attacks an adjacent character.
+    Attack(Entity),
+    /// Attacks a character in line of sight from afar, as far as the
+    /// actor's `Ai::range`.
+    Shoot(Entity),
+    Wait,
+    /// Picks up the item at this index of the tile the actor is on.
+    PickUp(usize),
+    /// Puts the carried item at this index on the ground.
+    Drop(usize),
+    /// Uses the carried item at this index.
+    Use(usize),
+    /// Throws one of the carried items at this index.
+    Throw(usize, Direction),
+    OpenDoor(Direction),
+    /// Goes up or down the stairs the actor is standing on. Only the player
+    /// can change levels.
+    TakeStairs(Stairs),
+}
+
+impl Action {
+    /// Energy the action takes, see `components::ACTION_ENERGY`.
+    pub fn cost(&self) -> i32 {
+        match self {
+            Action::PickUp(_) | Action::Drop(_) => ACTION_ENERGY / 2,
+            _ => ACTION_ENERGY,
+        }
+    }
+
+    /// How many steps away the action can be heard.
+    pub fn noise(&self) -> i32 {
+        match self {
+            Action::Attack(_) | Action::Shoot(_) => 6,
+            Action::Throw(_, _) => 5,
+            Action::OpenDoor(_) => 4,
+            Action::Move(_) | Action::Use(_) => 2,
+            Action::PickUp(_) | Action::Drop(_) => 1,
+            Action::Wait | Action::TakeStairs(_) => 0,
+        }
+    }
+}
+
+/// Something a monster may do, weighed against its other behaviours every
+/// turn, see `ai::propose`. Distances are in steps.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum Behaviour {
+    /// Walks up to the target and attacks it.
+    Hunt,
+    /// Walks around at random when there is nothing better to do.
+    Wander,
+    /// Runs from the target once health drops to this percentage of the
+    /// maximum.
+    Flee(i32),
+    /// Shoots the target from up to this distance, and backs off when the
+    /// target comes too close.
+    Ranged(i32),
+    /// Doesn't stray further than this from where it was spawned.
+    Guard(i32),
+    /// Takes on the targets of allies of the same team within this
+    /// distance, and stays close to them.
+    Pack(i32),
+}
+
+/// How two teams get along, see `resources::Factions`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Relation {
+    /// Attack each other on sight.
+    Hostile,
+    /// Leave each other alone until attacked.
+    Neutral,
+    Allied,
+}
+
+/// Why an `Action` could not be carried out. The messages are written for
+/// the player, other actors get them through `LastAction`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ActionError {
+    Stunned,
+    OutOfBounds,
+    Blocked(Terrain),
+    /// A character that isn't hostile is in the way.
+    Occupied,
+    NotAdjacent,
+    NotInSight,
+    OutOfRange,
+    NoTarget,
+    NoDoor,
+    NoStairs(Stairs),
+    NoItem,
+    NotUsable(String),
+    TooHeavy {
+        item: String,
+        carried: i32,
+    },
+}
+
+impl fmt::Display for ActionError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ActionError::Stunned => write!(f, "You are too dazed to act"),
+            ActionError::OutOfBounds => write!(f, "Movement out of bounds"),
+            ActionError::Blocked(terrain) => write!(f, "{} blocks the way", terrain.name()),
+            ActionError::Occupied => write!(f, "Movement blocked"),
+            ActionError::NotAdjacent => write!(f, "That is too far away"),
+            ActionError::NotInSight => write!(f, "You can't see that from here"),
+            ActionError::OutOfRange => write!(f, "That is out of range"),
+            ActionError::NoTarget => write!(f, "There is nothing to attack"),
+            ActionError::NoDoor => write!(f, "There is no closed door there"),
+            ActionError::NoStairs(stairs) => write!(f, "There are no stairs {} here", stairs),
+            ActionError::NoItem => write!(f, "There is no such item"),
+            ActionError::NotUsable(item) => write!(f, "{} can't be used", item),
+            ActionError::TooHeavy { item, carried } => write!(
+                f,
+                "{} is too heavy, you already carry {}/{}",
+                item, carried, CARRY_CAPACITY
+            ),
+        }
+    }
+}
+
+/// Where an item can be worn. Declaration order is display order.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub enum EquipSlot {
+    MainHand,
+    OffHand,
+    Head,
+    Body,
+    Ring,
+}
+
+impl EquipSlot {
+    pub fn name(self) -> &'static str {
+        match self {
+            EquipSlot::MainHand => "Main hand",
+            EquipSlot::OffHand => "Off hand",
+            EquipSlot::Head => "Head",
+            EquipSlot::Body => "Body",
+            EquipSlot::Ring => "Ring",
+        }
+    }
+
+    /// How many items can be worn in this slot at once.
+    pub fn capacity(self) -> usize {
+        match self {
+            EquipSlot::Ring => 2,
+            _ => 1,
+        }
+    }
+}
+
+/// Something that can happen to a character besides losing health.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum StatusEffect {
+    /// Skips this many turns.
+    Stun(u32),
+}
+
+impl StatusEffect {
+    pub fn description(self) -> String {
+        match self {
+            StatusEffect::Stun(turns) => format!("stuns {}", turns),
+        }
+    }
+}
+
+/// What happens to the user of a consumable, besides healing.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum UseEffect {
+    Apply(StatusEffect),
+    /// Removes all status effects.
+    Cure,
+    /// Moves the user to a random free spot on the level.
+    Teleport,
+    /// Marks the whole level as seen.
+    RevealMap,
+}
+
+impl UseEffect {
+    pub fn description(self) -> String {
+        match self {
+            UseEffect::Apply(effect) => format!("{} you", effect.description()),
+            UseEffect::Cure => "cures".to_owned(),
+            UseEffect::Teleport => "teleports".to_owned(),
+            UseEffect::RevealMap => "reveals the level".to_owned(),
+        }
+    }
+}
+
+/// Rough grouping of items, used to sort and filter the inventory.
+/// Declaration order is sort order.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum ItemKind {
+    Weapon,
+    Armor,
+    Consumable,
+    Misc,
+}
+
+impl ItemKind {
+    pub const ALL: [ItemKind; 4] = [
+        ItemKind::Weapon,
+        ItemKind::Armor,
+        ItemKind::Consumable,
+        ItemKind::Misc,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            ItemKind::Weapon => "weapons",
+            ItemKind::Armor => "armor",
+            ItemKind::Consumable => "consumables",
+            ItemKind::Misc => "misc",
+        }
+    }
 }
 
 #[derive(Default, Debug, Clone, PartialEq, Hash)]
 pub struct ItemProperties {
     pub name: Cow<'static, str>,
+    pub prefixes: Vec<Cow<'static, str>>,
+    pub suffixes: Vec<Cow<'static, str>>,
     pub damage: Option<i32>,
+    pub defense: Option<i32>,
+    pub heal: Option<i32>,
+    pub on_hit: Vec<StatusEffect>,
+    pub on_use: Vec<UseEffect>,
+    pub charges: Option<u32>,
+    pub weight: i32,
+    pub value: i32,
+    pub slot: Option<EquipSlot>,
+}
+
+impl ItemProperties {
+    pub fn is_usable(&self) -> bool {
+        self.heal.is_some() || !self.on_use.is_empty()
+    }
+
+    /// Only items that can't be worn are stacked.
+    pub fn is_stackable(&self) -> bool {
+        self.slot.is_none()
+    }
+
+    pub fn kind(&self) -> ItemKind {
+        match self.slot {
+            Some(EquipSlot::MainHand) => ItemKind::Weapon,
+            Some(_) => ItemKind::Armor,
+            None if self.is_usable() => ItemKind::Consumable,
+            None => ItemKind::Misc,
+        }
+    }
+
+    /// The name with all prefixes and suffixes, e.g. "Sharp dagger of biting".
+    pub fn full_name(&self) -> String {
+        let mut words: Vec<&str> = self.prefixes.iter().map(|x| &**x).collect();
+        if !self.name.is_empty() {
+            words.push(&self.name);
+        }
+        words.extend(self.suffixes.iter().map(|x| &**x));
+        let name = words.join(" ");
+
+        let mut chars = name.chars();
+        match chars.next() {
+            Some(first) => first.to_uppercase().chain(chars).collect(),
+            None => name,
+        }
+    }
 }
 
-#[derive(Debug, Clone, PartialEq, Hash)]
+#[derive(Debug, Clone, PartialEq, Hash, Serialize, Deserialize)]
 pub enum ItemPart {
     Name(Cow<'static, str>),
+    Prefix(Cow<'static, str>),
+    Suffix(Cow<'static, str>),
     Damage(i32),
+    Defense(i32),
+    Heal(i32),
+    OnHit(StatusEffect),
+    OnUse(UseEffect),
+    Charges(u32),
+    Weight(i32),
+    Value(i32),
+    Slot(EquipSlot),
 }
 
 impl ItemPart {
@@ -46,9 +477,39 @@ impl ItemPart {
             Name(ref name) => {
                 prop.name.to_mut().push_str(name);
             }
+            Prefix(ref prefix) => {
+                prop.prefixes.push(prefix.clone());
+            }
+            Suffix(ref suffix) => {
+                prop.suffixes.push(suffix.clone());
+            }
             Damage(dmg) => {
                 prop.damage = Some(prop.damage.map_or(dmg, |x| x + dmg));
             }
+            Defense(def) => {
+                prop.defense = Some(prop.defense.map_or(def, |x| x + def));
+            }
+            Heal(amount) => {
+                prop.heal = Some(prop.heal.map_or(amount, |x| x + amount));
+            }
+            OnHit(effect) => {
+                prop.on_hit.push(effect);
+            }
+            OnUse(effect) => {
+                prop.on_use.push(effect);
+            }
+            Charges(charges) => {
+                prop.charges = Some(prop.charges.map_or(charges, |x| x + charges));
+            }
+            Weight(weight) => {
+                prop.weight += weight;
+            }
+            Value(value) => {
+                prop.value += value;
+            }
+            Slot(slot) => {
+                prop.slot = Some(slot);
+            }
         }
     }
 }
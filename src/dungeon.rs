@@ -0,0 +1,141 @@
+use amethyst::prelude::*;
+use rand::Rng;
+
+use crate::{
+    components::{Character, Item, WorldPosition},
+    data::Terrain,
+    fov::{self, PLAYER_SIGHT_RADIUS},
+    mapgen,
+    play::initialise_enemy,
+    resources::{Board, Dungeon, GameRng, LogEvents, PlayerEntity, WorldItem, WorldMap},
+    save::{capture_entities, restore_entity, SavedLevel, SavedMap},
+    systems::OldWorldPosition,
+};
+
+pub const LEVEL_WIDTH: usize = 20;
+pub const LEVEL_HEIGHT: usize = 20;
+
+/// A new map for the level at `depth`. Every level has stairs down, all
+/// but the top one have stairs up.
+pub fn generate_level<R: Rng>(depth: usize, rng: &mut R) -> WorldMap {
+    let mut map = mapgen::generate(LEVEL_WIDTH, LEVEL_HEIGHT, rng);
+    mapgen::place_stairs(&mut map, rng, depth > 0);
+    map
+}
+
+/// Moves the player one level down or up. Everything else on the current
+/// level is frozen into the `Dungeon` and the target level is thawed, or
+/// generated if it is visited for the first time.
+pub fn change_level(world: &mut World, down: bool) {
+    let player = match world.read_resource::<PlayerEntity>().0 {
+        Some(player) => player,
+        None => return,
+    };
+    let depth = world.read_resource::<Dungeon>().depth;
+    let target = match (down, depth) {
+        (true, _) => depth + 1,
+        (false, 0) => return,
+        (false, _) => depth - 1,
+    };
+
+    let (frozen, saved): (Vec<_>, Vec<_>) = capture_entities(world)
+        .into_iter()
+        .filter(|(_, saved)| !saved.player)
+        .unzip();
+    world
+        .delete_entities(&frozen)
+        .expect("Failed to freeze the level");
+    let level = SavedLevel {
+        map: SavedMap::from_map(&world.read_resource::<WorldMap>()),
+        entities: saved,
+    };
+    world.write_resource::<Dungeon>().freeze(depth, level);
+
+    let thawed = world.write_resource::<Dungeon>().thaw(target);
+    let generated = thawed.is_none();
+    let map = match &thawed {
+        Some(level) => level.map.to_map(),
+        None => generate_level(target, &mut *world.write_resource::<GameRng>()),
+    };
+    world.add_resource(map);
+    world.write_resource::<Dungeon>().depth = target;
+
+    let board = world.read_resource::<Board>().0.unwrap();
+    if let Some(level) = thawed {
+        for saved in level.entities {
+            restore_entity(world, board, saved);
+        }
+    }
+
+    // The old position belongs to the previous map. The characters that
+    // were frozen here take their tiles back before the player arrives.
+    world.write_storage::<WorldPosition>().remove(player);
+    world.write_storage::<OldWorldPosition>().remove(player);
+    register_on_map(world);
+
+    // Arrive on the stairs leading back, or as close to them as possible
+    // if somebody is standing there.
+    let arrival = if down {
+        Terrain::StairsUp
+    } else {
+        Terrain::StairsDown
+    };
+    let position = {
+        let map = world.read_resource::<WorldMap>();
+        mapgen::find_terrain(&map, arrival)
+            .and_then(|stairs| mapgen::find_free_near(&map, &stairs))
+            .or_else(|| mapgen::find_spawn(&map, &mut *world.write_resource::<GameRng>(), None))
+            .expect("Level has no room for the player")
+    };
+    world
+        .write_storage::<WorldPosition>()
+        .insert(player, position)
+        .ok();
+    register_on_map(world);
+
+    if generated {
+        for _ in 0..=target {
+            initialise_enemy(world);
+            // Taken tiles are only skipped once they are on the map.
+            register_on_map(world);
+        }
+    }
+
+    fov::update_visibility(
+        &mut world.write_resource::<WorldMap>(),
+        &position,
+        PLAYER_SIGHT_RADIUS,
+    );
+    world.read_resource::<LogEvents>().send(format!(
+        "You go {} to depth {}",
+        if down { "down" } else { "up" },
+        target + 1
+    ));
+}
+
+/// Puts characters and items on their tiles right away, instead of waiting
+/// for the next turn's `ApplyWorldMapSystem`, which won't mind.
+fn register_on_map(world: &mut World) {
+    let entities = world.entities();
+    let position = world.read_storage::<WorldPosition>();
+    let character = world.read_storage::<Character>();
+    let item = world.read_storage::<Item>();
+    let mut map = world.write_resource::<WorldMap>();
+
+    for (entity, pos) in (&*entities, &position).join() {
+        let tile = match map.get_mut(pos) {
+            Some(tile) => tile,
+            None => continue,
+        };
+        if character.contains(entity) {
+            tile.character = Some(entity);
+        } else if let Some(item) = item.get(entity) {
+            if !tile.items.iter().any(|x| x.entity == entity) {
+                tile.items.push(WorldItem {
+                    entity,
+                    item: item.clone(),
+                });
+            }
+        }
+    }
+}
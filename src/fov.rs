@@ -0,0 +1,204 @@
+use crate::{components::WorldPosition, data::TileVisibility, resources::WorldMap};
+
+pub const PLAYER_SIGHT_RADIUS: i32 = 8;
+pub const MONSTER_SIGHT_RADIUS: i32 = 6;
+
+/// Octant transforms as (xx, xy, yx, yy).
+const OCTANTS: [[i32; 4]; 8] = [
+    [1, 0, 0, 1],
+    [0, 1, 1, 0],
+    [0, -1, 1, 0],
+    [-1, 0, 0, 1],
+    [-1, 0, 0, -1],
+    [0, -1, -1, 0],
+    [0, 1, -1, 0],
+    [1, 0, 0, -1],
+];
+
+/// Marks what is visible from `origin` as visible and what was visible
+/// before as remembered.
+pub fn update_visibility(map: &mut WorldMap, origin: &WorldPosition, radius: i32) {
+    for row in &mut map.tiles {
+        for tile in row {
+            if tile.visibility == TileVisibility::Visible {
+                tile.visibility = TileVisibility::Remembered;
+            }
+        }
+    }
+
+    let mut visible = Vec::new();
+    compute(map, origin, radius, |pos| visible.push(pos));
+    for pos in visible {
+        if let Some(tile) = map.get_mut(&pos) {
+            tile.visibility = TileVisibility::Visible;
+        }
+    }
+}
+
+/// Whether `to` is in line of sight of `from`, at any distance.
+pub fn can_see(map: &WorldMap, from: &WorldPosition, to: &WorldPosition) -> bool {
+    // Shadowcasting uses euclidean distance, this radius covers `to` in any
+    // direction.
+    let radius = (from.x - to.x).abs() + (from.y - to.y).abs();
+    let mut seen = false;
+    compute(map, from, radius, |pos| seen |= pos == *to);
+    seen
+}
+
+/// Recursive shadowcasting. Calls `visit` once for every position visible
+/// from `origin` within `radius`, including `origin` itself.
+/// Positions may be visited more than once.
+pub fn compute<F>(map: &WorldMap, origin: &WorldPosition, radius: i32, mut visit: F)
+where
+    F: FnMut(WorldPosition),
+{
+    if map.is_legal_pos(origin) {
+        visit(*origin);
+    }
+    let caster = Caster {
+        map,
+        origin: *origin,
+        radius,
+    };
+    for octant in OCTANTS.iter() {
+        caster.cast_light(1, 1.0, 0.0, octant, &mut visit);
+    }
+}
+
+struct Caster<'a> {
+    map: &'a WorldMap,
+    origin: WorldPosition,
+    radius: i32,
+}
+
+impl<'a> Caster<'a> {
+    fn cast_light<F>(&self, row: i32, mut start: f32, end: f32, octant: &[i32; 4], visit: &mut F)
+    where
+        F: FnMut(WorldPosition),
+    {
+        let Caster {
+            map,
+            origin,
+            radius,
+        } = *self;
+        if start < end {
+            return;
+        }
+        let radius_sq = radius * radius;
+        let mut new_start = 0.0;
+
+        for j in row..=radius {
+            let dy = -j;
+            let mut dx = -j - 1;
+            let mut blocked = false;
+
+            while dx <= 0 {
+                dx += 1;
+                let left_slope = (dx as f32 - 0.5) / (dy as f32 + 0.5);
+                let right_slope = (dx as f32 + 0.5) / (dy as f32 - 0.5);
+                if start < right_slope {
+                    continue;
+                } else if end > left_slope {
+                    break;
+                }
+
+                let pos = WorldPosition::new(
+                    origin.x + dx * octant[0] + dy * octant[1],
+                    origin.y + dx * octant[2] + dy * octant[3],
+                );
+                if dx * dx + dy * dy <= radius_sq && map.is_legal_pos(&pos) {
+                    visit(pos);
+                }
+
+                let opaque = map
+                    .terrain(&pos)
+                    .map_or(true, |terrain| terrain.is_opaque());
+                if blocked {
+                    if opaque {
+                        new_start = right_slope;
+                    } else {
+                        blocked = false;
+                        start = new_start;
+                    }
+                } else if opaque && j < radius {
+                    blocked = true;
+                    self.cast_light(j + 1, start, left_slope, octant, visit);
+                    new_start = right_slope;
+                }
+            }
+
+            if blocked {
+                break;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn visible_from(map: &WorldMap, origin: &WorldPosition, radius: i32) -> Vec<WorldPosition> {
+        let mut visible = Vec::new();
+        compute(map, origin, radius, |pos| visible.push(pos));
+        visible
+    }
+
+    #[test]
+    fn sees_the_whole_open_room() {
+        let map = WorldMap::from_rows(&[".....", ".....", ".....", ".....", "....."]);
+        let visible = visible_from(&map, &WorldPosition::new(2, 2), PLAYER_SIGHT_RADIUS);
+        for y in 0..5 {
+            for x in 0..5 {
+                assert!(
+                    visible.contains(&WorldPosition::new(x, y)),
+                    "({}, {})",
+                    x,
+                    y
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn stays_within_the_radius() {
+        let map = WorldMap::from_rows(&["........"]);
+        let visible = visible_from(&map, &WorldPosition::new(0, 0), 3);
+        assert!(visible.contains(&WorldPosition::new(3, 0)));
+        assert!(!visible.contains(&WorldPosition::new(4, 0)));
+    }
+
+    #[test]
+    fn walls_block_sight() {
+        let map = WorldMap::from_rows(&[".....", ".....", "..#..", ".....", "....."]);
+        let origin = WorldPosition::new(0, 2);
+        let visible = visible_from(&map, &origin, PLAYER_SIGHT_RADIUS);
+        assert!(visible.contains(&WorldPosition::new(2, 2)));
+        assert!(!visible.contains(&WorldPosition::new(4, 2)));
+
+        assert!(!can_see(&map, &origin, &WorldPosition::new(4, 2)));
+        assert!(can_see(&map, &origin, &WorldPosition::new(4, 1)));
+    }
+
+    #[test]
+    fn can_see_ignores_the_sight_radius() {
+        let map = WorldMap::from_rows(&["...................."]);
+        let from = WorldPosition::new(0, 0);
+        let to = WorldPosition::new(19, 0);
+        assert!(from.distance(&to) > PLAYER_SIGHT_RADIUS);
+        assert!(can_see(&map, &from, &to));
+    }
+
+    #[test]
+    fn update_visibility_remembers_what_went_out_of_sight() {
+        let mut map = WorldMap::from_rows(&["..........", "..........", ".........."]);
+        update_visibility(&mut map, &WorldPosition::new(1, 1), 2);
+        update_visibility(&mut map, &WorldPosition::new(8, 1), 2);
+
+        let visibility = |x, y| map.get(&WorldPosition::new(x, y)).unwrap().visibility;
+        assert_eq!(visibility(0, 1), TileVisibility::Remembered);
+        assert_eq!(visibility(5, 1), TileVisibility::Unseen);
+        assert_eq!(visibility(9, 1), TileVisibility::Visible);
+        assert!(map.is_visible(&WorldPosition::new(8, 1)));
+    }
+}
@@ -5,22 +5,47 @@ extern crate shred_derive;
 extern crate crossbeam_channel;
 extern crate easycurses;
 extern crate hibitset;
+extern crate pancurses;
 extern crate rand;
+extern crate ron;
+extern crate serde;
+#[macro_use]
+extern crate serde_derive;
 extern crate specs_hierarchy;
+extern crate unicode_segmentation;
+extern crate unicode_width;
 
+mod ai;
 mod bundles;
 mod components;
 mod data;
+mod dungeon;
+mod fov;
+mod mapgen;
+mod options;
+mod pathfinding;
 mod play;
+mod replay;
 mod resources;
+mod save;
+mod spawner;
 mod specs_ext;
 mod system_chain;
 mod systems;
+mod templates;
 mod tui;
 
 use crate::{
     bundles::{LiveBundle, TickBundle},
-    play::PlayState,
+    options::Options,
+    play::{ExitErrors, PlayState},
+    tui::{CursesBackend, Key, MemoryBackend, SharedBackend},
+};
+
+use std::{
+    cell::RefCell,
+    io::{self, Read},
+    rc::Rc,
 };
 
 use amethyst::{
@@ -93,16 +118,90 @@ impl<'a, 'b> DataInit<CustomGameData<'a, 'b>> for CustomGameDataBuilder<'a, 'b>
 }
 
 fn main() -> amethyst::Result<()> {
+    let options = match Options::from_args() {
+        Ok(options) => options,
+        Err(err) => {
+            eprintln!("{}", err);
+            eprintln!(
+                "Usage: rogue [--seed <number>] [--headless] [--record <file> | --replay <file>]"
+            );
+            std::process::exit(2);
+        }
+    };
+
+    if let Some(path) = &options.replay {
+        match replay::run(path) {
+            Ok(state) => println!("{}", state),
+            Err(err) => {
+                eprintln!("Could not replay {:?}: {}", path, err);
+                std::process::exit(1);
+            }
+        }
+        return Ok(());
+    }
+
     amethyst::start_logger(Default::default());
 
+    let headless = if options.headless {
+        match headless_backend() {
+            Ok(backend) => Some(Rc::new(RefCell::new(backend))),
+            Err(err) => {
+                eprintln!("Could not read keys from stdin: {}", err);
+                std::process::exit(1);
+            }
+        }
+    } else {
+        None
+    };
+    let backend: SharedBackend = match &headless {
+        Some(backend) => backend.clone(),
+        None => Rc::new(RefCell::new(CursesBackend::initialize())),
+    };
+
     let game_data = CustomGameDataBuilder::default()
-        .with_live_bundle(LiveBundle::default())?
+        .with_live_bundle(LiveBundle::new(backend))?
         .with_tick_bundle(TickBundle::default())?;
-    let mut game = Application::build("./", PlayState)?
+    let errors = ExitErrors::default();
+    let mut game = Application::build("./", PlayState::new(options, errors.clone()))?
         .with_frame_limit(FrameRateLimitStrategy::Sleep, 30)
         .build(game_data)?;
 
     game.run();
+    // Dropping the game shuts curses down, after that the terminal can be
+    // written to again.
+    drop(game);
+    for err in errors.borrow().iter() {
+        eprintln!("{}", err);
+    }
+
+    if let Some(backend) = headless {
+        for row in backend.borrow().rows() {
+            println!("{}", row.trim_end());
+        }
+    }
 
     Ok(())
 }
+
+const HEADLESS_SCREEN_SIZE: (i32, i32) = (80, 24);
+/// Empty frames between scripted keys, enough for the held key delay to pass.
+const HEADLESS_KEY_GAP: usize = 10;
+
+/// A memory backend scripted with the keys from stdin, followed by Escape so
+/// the game quits once the script runs out.
+fn headless_backend() -> io::Result<MemoryBackend> {
+    let mut script = String::new();
+    io::stdin().read_to_string(&mut script)?;
+
+    let (width, height) = HEADLESS_SCREEN_SIZE;
+    let mut backend = MemoryBackend::new(width, height);
+    backend.push_keys(
+        script
+            .chars()
+            .filter(|c| !c.is_control())
+            .chain(Some('\u{1b}'))
+            .map(Key::Character),
+        HEADLESS_KEY_GAP,
+    );
+    Ok(backend)
+}
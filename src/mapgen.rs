@@ -0,0 +1,486 @@
+use rand::Rng;
+
+use crate::{components::WorldPosition, data::Terrain, resources::WorldMap};
+
+const MIN_ROOM_SIZE: i32 = 3;
+const MAX_ROOM_SIZE: i32 = 7;
+const ROOM_ATTEMPTS: u32 = 40;
+
+const CAVE_FILL_CHANCE: f64 = 0.45;
+const CAVE_SMOOTHING_STEPS: u32 = 5;
+const CAVE_ATTEMPTS: u32 = 10;
+/// Caves whose largest open region covers less than 1/N of the map are rejected.
+const CAVE_MIN_OPEN_FRACTION: usize = 3;
+const CAVE_SECTORS: i32 = 3;
+
+const DOOR_CHANCE: f64 = 0.5;
+const POOL_CHANCE: f64 = 0.3;
+const RUBBLE_CHANCE: f64 = 0.03;
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Generator {
+    RoomsAndCorridors,
+    Caves,
+}
+
+/// A rectangular area of the map. Rooms are where things get spawned.
+#[derive(Debug, Copy, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Room {
+    pub x: i32,
+    pub y: i32,
+    pub width: i32,
+    pub height: i32,
+}
+
+impl Room {
+    pub fn new(x: i32, y: i32, width: i32, height: i32) -> Self {
+        Room {
+            x,
+            y,
+            width,
+            height,
+        }
+    }
+
+    pub fn center(&self) -> WorldPosition {
+        WorldPosition::new(self.x + self.width / 2, self.y + self.height / 2)
+    }
+
+    pub fn contains(&self, pos: &WorldPosition) -> bool {
+        pos.x >= self.x
+            && pos.x < self.x + self.width
+            && pos.y >= self.y
+            && pos.y < self.y + self.height
+    }
+
+    /// True if the rooms overlap or touch, so that at least one wall
+    /// tile is always left between two rooms.
+    pub fn intersects(&self, other: &Room) -> bool {
+        self.x <= other.x + other.width
+            && other.x <= self.x + self.width
+            && self.y <= other.y + other.height
+            && other.y <= self.y + self.height
+    }
+
+    pub fn positions(&self) -> impl Iterator<Item = WorldPosition> {
+        let Room {
+            x,
+            y,
+            width,
+            height,
+        } = *self;
+        (y..y + height).flat_map(move |py| (x..x + width).map(move |px| WorldPosition::new(px, py)))
+    }
+}
+
+/// Generates a new level, picking the generator at random.
+pub fn generate<R: Rng>(width: usize, height: usize, rng: &mut R) -> WorldMap {
+    let generator = if rng.gen_bool(0.5) {
+        Generator::RoomsAndCorridors
+    } else {
+        Generator::Caves
+    };
+    generate_with(generator, width, height, rng)
+}
+
+pub fn generate_with<R: Rng>(
+    generator: Generator,
+    width: usize,
+    height: usize,
+    rng: &mut R,
+) -> WorldMap {
+    let mut map = WorldMap::new(width, height);
+    match generator {
+        Generator::RoomsAndCorridors => rooms_and_corridors(&mut map, rng),
+        Generator::Caves => {
+            if !caves(&mut map, rng) {
+                rooms_and_corridors(&mut map, rng);
+            }
+        }
+    }
+    scatter_rubble(&mut map, rng);
+    map
+}
+
+/// Picks a random free floor tile inside one of the map's rooms.
+/// Rooms containing `avoid` are skipped unless there is nowhere else to go.
+pub fn find_spawn<R: Rng>(
+    map: &WorldMap,
+    rng: &mut R,
+    avoid: Option<&WorldPosition>,
+) -> Option<WorldPosition> {
+    let candidates = |skip_avoided: bool| {
+        map.rooms
+            .iter()
+            .filter(|room| !skip_avoided || avoid.map_or(true, |pos| !room.contains(pos)))
+            .flat_map(|room| room.positions())
+            .filter(|pos| avoid != Some(pos))
+            .filter(|pos| {
+                map.get(pos).map_or(false, |tile| {
+                    tile.terrain == Terrain::Floor && tile.character.is_none()
+                })
+            })
+            .collect::<Vec<_>>()
+    };
+
+    let mut positions = candidates(true);
+    if positions.is_empty() {
+        positions = candidates(false);
+    }
+    if positions.is_empty() {
+        None
+    } else {
+        Some(positions[rng.gen_range(0, positions.len())])
+    }
+}
+
+/// Puts stairs down, and stairs up if `up` is set, in different rooms
+/// where possible.
+pub fn place_stairs<R: Rng>(map: &mut WorldMap, rng: &mut R, up: bool) {
+    let down = find_spawn(map, rng, None);
+    if let Some(pos) = down {
+        map.set_terrain(&pos, Terrain::StairsDown);
+    }
+    if up {
+        if let Some(pos) = find_spawn(map, rng, down.as_ref()) {
+            map.set_terrain(&pos, Terrain::StairsUp);
+        }
+    }
+}
+
+/// The free passable tile closest to `pos`, which is `pos` itself if it
+/// is free.
+pub fn find_free_near(map: &WorldMap, pos: &WorldPosition) -> Option<WorldPosition> {
+    (0..map.height)
+        .flat_map(|y| (0..map.width).map(move |x| WorldPosition::new(x as i32, y as i32)))
+        .filter(|candidate| {
+            map.get(candidate).map_or(false, |tile| {
+                tile.terrain.is_passable() && tile.character.is_none()
+            })
+        })
+        .min_by_key(|candidate| candidate.distance(pos))
+}
+
+/// The first tile with the given terrain, if any.
+pub fn find_terrain(map: &WorldMap, terrain: Terrain) -> Option<WorldPosition> {
+    map.tiles.iter().enumerate().find_map(|(y, row)| {
+        row.iter()
+            .position(|tile| tile.terrain == terrain)
+            .map(|x| WorldPosition::new(x as i32, y as i32))
+    })
+}
+
+fn fill(map: &mut WorldMap, terrain: Terrain) {
+    for row in &mut map.tiles {
+        for tile in row {
+            tile.terrain = terrain;
+        }
+    }
+}
+
+fn rooms_and_corridors<R: Rng>(map: &mut WorldMap, rng: &mut R) {
+    fill(map, Terrain::Wall);
+    let (map_width, map_height) = (map.width as i32, map.height as i32);
+
+    let mut rooms: Vec<Room> = Vec::new();
+    for _ in 0..ROOM_ATTEMPTS {
+        let width = rng.gen_range(MIN_ROOM_SIZE, MAX_ROOM_SIZE + 1);
+        let height = rng.gen_range(MIN_ROOM_SIZE, MAX_ROOM_SIZE + 1);
+        if width + 2 > map_width || height + 2 > map_height {
+            continue;
+        }
+        let room = Room::new(
+            rng.gen_range(1, map_width - width),
+            rng.gen_range(1, map_height - height),
+            width,
+            height,
+        );
+        if rooms.iter().any(|other| other.intersects(&room)) {
+            continue;
+        }
+
+        for pos in room.positions() {
+            map.set_terrain(&pos, Terrain::Floor);
+        }
+        if let Some(previous) = rooms.last() {
+            let horizontal_first = rng.gen_bool(0.5);
+            carve_corridor(map, previous.center(), room.center(), horizontal_first);
+        }
+        rooms.push(room);
+    }
+
+    map.rooms = rooms;
+    place_doors(map, rng);
+    place_pools(map, rng);
+}
+
+fn carve_corridor(
+    map: &mut WorldMap,
+    from: WorldPosition,
+    to: WorldPosition,
+    horizontal_first: bool,
+) {
+    let corner = if horizontal_first {
+        WorldPosition::new(to.x, from.y)
+    } else {
+        WorldPosition::new(from.x, to.y)
+    };
+    for (start, end) in [(from, corner), (corner, to)].iter() {
+        for x in start.x.min(end.x)..=start.x.max(end.x) {
+            for y in start.y.min(end.y)..=start.y.max(end.y) {
+                map.set_terrain(&WorldPosition::new(x, y), Terrain::Floor);
+            }
+        }
+    }
+}
+
+/// Turns some corridor openings next to rooms into closed doors.
+fn place_doors<R: Rng>(map: &mut WorldMap, rng: &mut R) {
+    let is_wall = |map: &WorldMap, x: i32, y: i32| {
+        map.terrain(&WorldPosition::new(x, y)) == Some(Terrain::Wall)
+    };
+
+    for room in map.rooms.clone() {
+        let horizontal_edges = (room.x..room.x + room.width)
+            .flat_map(|x| vec![(x, room.y - 1), (x, room.y + room.height)])
+            .map(|(x, y)| (x, y, true));
+        let vertical_edges = (room.y..room.y + room.height)
+            .flat_map(|y| vec![(room.x - 1, y), (room.x + room.width, y)])
+            .map(|(x, y)| (x, y, false));
+
+        for (x, y, horizontal) in horizontal_edges.chain(vertical_edges) {
+            let pos = WorldPosition::new(x, y);
+            if map.terrain(&pos) != Some(Terrain::Floor) {
+                continue;
+            }
+            let framed = if horizontal {
+                is_wall(map, x - 1, y) && is_wall(map, x + 1, y)
+            } else {
+                is_wall(map, x, y - 1) && is_wall(map, x, y + 1)
+            };
+            if framed && rng.gen_bool(DOOR_CHANCE) {
+                map.set_terrain(&pos, Terrain::ClosedDoor);
+            }
+        }
+    }
+}
+
+/// Floods parts of some rooms. Only the inside of a room is flooded, so
+/// the ring of floor along its walls keeps every entrance reachable.
+fn place_pools<R: Rng>(map: &mut WorldMap, rng: &mut R) {
+    for room in map.rooms.clone() {
+        if room.width < 5 || room.height < 5 || !rng.gen_bool(POOL_CHANCE) {
+            continue;
+        }
+        let inner = Room::new(room.x + 1, room.y + 1, room.width - 2, room.height - 2);
+        let center = inner.center();
+        let radius = rng.gen_range(1, inner.width.min(inner.height) / 2 + 2);
+        for pos in inner.positions() {
+            if (pos.x - center.x).abs() + (pos.y - center.y).abs() < radius {
+                map.set_terrain(&pos, Terrain::Water);
+            }
+        }
+    }
+}
+
+fn scatter_rubble<R: Rng>(map: &mut WorldMap, rng: &mut R) {
+    for row in &mut map.tiles {
+        for tile in row {
+            if tile.terrain == Terrain::Floor && rng.gen_bool(RUBBLE_CHANCE) {
+                tile.terrain = Terrain::Rubble;
+            }
+        }
+    }
+}
+
+/// Cellular automata caves. Returns false if no usable cave was produced.
+fn caves<R: Rng>(map: &mut WorldMap, rng: &mut R) -> bool {
+    let (width, height) = (map.width as i32, map.height as i32);
+
+    for _ in 0..CAVE_ATTEMPTS {
+        let mut walls: Vec<Vec<bool>> = (0..height)
+            .map(|y| {
+                (0..width)
+                    .map(|x| {
+                        x == 0
+                            || y == 0
+                            || x == width - 1
+                            || y == height - 1
+                            || rng.gen_bool(CAVE_FILL_CHANCE)
+                    })
+                    .collect()
+            })
+            .collect();
+
+        for _ in 0..CAVE_SMOOTHING_STEPS {
+            walls = (0..height)
+                .map(|y| {
+                    (0..width)
+                        .map(|x| {
+                            let mut count = 0;
+                            for ny in y - 1..=y + 1 {
+                                for nx in x - 1..=x + 1 {
+                                    if nx < 0
+                                        || ny < 0
+                                        || nx >= width
+                                        || ny >= height
+                                        || walls[ny as usize][nx as usize]
+                                    {
+                                        count += 1;
+                                    }
+                                }
+                            }
+                            count >= 5 || x == 0 || y == 0 || x == width - 1 || y == height - 1
+                        })
+                        .collect()
+                })
+                .collect();
+        }
+
+        let region = largest_open_region(&walls);
+        if region.len() * CAVE_MIN_OPEN_FRACTION < map.width * map.height {
+            continue;
+        }
+
+        fill(map, Terrain::Wall);
+        for pos in &region {
+            map.set_terrain(pos, Terrain::Floor);
+        }
+
+        let (sector_width, sector_height) = (width / CAVE_SECTORS, height / CAVE_SECTORS);
+        map.rooms = (0..CAVE_SECTORS)
+            .flat_map(|sy| {
+                (0..CAVE_SECTORS).map(move |sx| {
+                    Room::new(
+                        sx * sector_width,
+                        sy * sector_height,
+                        sector_width,
+                        sector_height,
+                    )
+                })
+            })
+            .filter(|room| region.iter().any(|pos| room.contains(pos)))
+            .collect();
+        return true;
+    }
+    false
+}
+
+fn largest_open_region(walls: &[Vec<bool>]) -> Vec<WorldPosition> {
+    let height = walls.len();
+    let width = walls.first().map_or(0, |row| row.len());
+    let mut visited = vec![vec![false; width]; height];
+    let mut best = Vec::new();
+
+    for y in 0..height {
+        for x in 0..width {
+            if walls[y][x] || visited[y][x] {
+                continue;
+            }
+            let mut region = Vec::new();
+            let mut stack = vec![(x, y)];
+            visited[y][x] = true;
+            while let Some((cx, cy)) = stack.pop() {
+                region.push(WorldPosition::new(cx as i32, cy as i32));
+                let neighbours = [
+                    (cx.wrapping_sub(1), cy),
+                    (cx + 1, cy),
+                    (cx, cy.wrapping_sub(1)),
+                    (cx, cy + 1),
+                ];
+                for &(nx, ny) in neighbours.iter() {
+                    if nx < width && ny < height && !walls[ny][nx] && !visited[ny][nx] {
+                        visited[ny][nx] = true;
+                        stack.push((nx, ny));
+                    }
+                }
+            }
+            if region.len() > best.len() {
+                best = region;
+            }
+        }
+    }
+    best
+}
+
+#[cfg(test)]
+mod tests {
+    use amethyst::prelude::*;
+
+    use super::*;
+    use crate::{data::Direction, resources::GameRng};
+
+    /// Tiles a character can get to, walking through doors.
+    fn is_walkable(map: &WorldMap, pos: &WorldPosition) -> bool {
+        map.terrain(pos).map_or(false, |terrain| {
+            terrain.is_passable() || terrain == Terrain::ClosedDoor
+        })
+    }
+
+    fn assert_connected(map: &WorldMap) {
+        let walkable: Vec<_> = (0..map.height as i32)
+            .flat_map(|y| (0..map.width as i32).map(move |x| WorldPosition::new(x, y)))
+            .filter(|pos| is_walkable(map, pos))
+            .collect();
+        assert!(!walkable.is_empty());
+
+        let mut reached = vec![walkable[0]];
+        let mut stack = vec![walkable[0]];
+        while let Some(pos) = stack.pop() {
+            for dir in Direction::ALL.iter() {
+                let next = pos.step_dir(*dir);
+                if is_walkable(map, &next) && !reached.contains(&next) {
+                    reached.push(next);
+                    stack.push(next);
+                }
+            }
+        }
+        assert_eq!(reached.len(), walkable.len());
+    }
+
+    #[test]
+    fn rooms_and_corridors_are_connected() {
+        for seed in 0..20 {
+            let mut rng = GameRng::new(seed);
+            assert_connected(&generate_with(
+                Generator::RoomsAndCorridors,
+                40,
+                20,
+                &mut rng,
+            ));
+        }
+    }
+
+    #[test]
+    fn caves_are_connected() {
+        for seed in 0..20 {
+            let mut rng = GameRng::new(seed);
+            assert_connected(&generate_with(Generator::Caves, 40, 20, &mut rng));
+        }
+    }
+
+    #[test]
+    fn stairs_are_reachable() {
+        for seed in 0..20 {
+            let mut rng = GameRng::new(seed);
+            let mut map = generate(40, 20, &mut rng);
+            place_stairs(&mut map, &mut rng, true);
+            assert!(find_terrain(&map, Terrain::StairsDown).is_some());
+            assert!(find_terrain(&map, Terrain::StairsUp).is_some());
+            assert_connected(&map);
+        }
+    }
+
+    #[test]
+    fn find_free_near_skips_occupied_and_blocked_tiles() {
+        let mut map = WorldMap::from_rows(&["#....", "....."]);
+        let stairs = WorldPosition::new(1, 0);
+        assert_eq!(find_free_near(&map, &stairs), Some(stairs));
+
+        let mut world = World::new();
+        map.tiles[0][1].character = Some(world.create_entity().build());
+        let free = find_free_near(&map, &stairs).unwrap();
+        assert_eq!(free.distance(&stairs), 1);
+        assert_ne!(free, WorldPosition::new(0, 0));
+    }
+}
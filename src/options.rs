@@ -0,0 +1,46 @@
+use std::{env, path::PathBuf};
+
+/// Command line options.
+#[derive(Default, Debug, Clone)]
+pub struct Options {
+    /// Seed for a new game. Ignored when a save is loaded.
+    pub seed: Option<u64>,
+    /// Record the session's input to this file.
+    pub record: Option<PathBuf>,
+    /// Replay a recording without a terminal and print the final state.
+    pub replay: Option<PathBuf>,
+    /// Run without a terminal, reading keys from stdin and printing the
+    /// final screen.
+    pub headless: bool,
+}
+
+impl Options {
+    pub fn from_args() -> Result<Options, String> {
+        let mut options = Options::default();
+        let mut args = env::args().skip(1);
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--seed" => {
+                    let value = args.next().ok_or("--seed needs a value")?;
+                    options.seed = Some(
+                        value
+                            .parse()
+                            .map_err(|_| format!("Invalid seed: {}", value))?,
+                    );
+                }
+                "--headless" => options.headless = true,
+                "--record" => {
+                    options.record = Some(args.next().ok_or("--record needs a path")?.into());
+                }
+                "--replay" => {
+                    options.replay = Some(args.next().ok_or("--replay needs a path")?.into());
+                }
+                _ => return Err(format!("Unknown argument: {}", arg)),
+            }
+        }
+        if options.record.is_some() && options.replay.is_some() {
+            return Err("--record and --replay can't be combined".to_owned());
+        }
+        Ok(options)
+    }
+}
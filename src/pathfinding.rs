@@ -0,0 +1,148 @@
+use std::{
+    cmp::Reverse,
+    collections::{BinaryHeap, HashMap},
+};
+
+use crate::{
+    components::WorldPosition,
+    data::{Direction, Terrain},
+    resources::{WorldMap, WorldTile},
+};
+
+/// Finds the cheapest path from `from` to `to` with A*.
+///
+/// `cost` gives the cost of stepping onto a tile, or `None` if the tile
+/// can't be entered at all. The returned path excludes `from` and ends
+/// at `to`.
+pub fn find_path<F>(
+    map: &WorldMap,
+    from: &WorldPosition,
+    to: &WorldPosition,
+    cost: F,
+) -> Option<Vec<WorldPosition>>
+where
+    F: Fn(&WorldPosition, &WorldTile) -> Option<u32>,
+{
+    if !map.is_legal_pos(from) || !map.is_legal_pos(to) {
+        return None;
+    }
+
+    let mut open = BinaryHeap::new();
+    let mut came_from: HashMap<WorldPosition, WorldPosition> = HashMap::new();
+    let mut best: HashMap<WorldPosition, u32> = HashMap::new();
+
+    best.insert(*from, 0);
+    open.push(Reverse((heuristic(from, to), 0, from.y, from.x)));
+
+    while let Some(Reverse((_, cost_so_far, y, x))) = open.pop() {
+        let pos = WorldPosition::new(x, y);
+        if pos == *to {
+            let mut path = vec![pos];
+            let mut current = pos;
+            while let Some(previous) = came_from.get(&current) {
+                if previous == from {
+                    break;
+                }
+                path.push(*previous);
+                current = *previous;
+            }
+            path.reverse();
+            return Some(path);
+        }
+        if best.get(&pos).map_or(false, |best| cost_so_far > *best) {
+            continue;
+        }
+
+        for dir in Direction::ALL.iter() {
+            let next = pos.step_dir(*dir);
+            let step_cost = match map.get(&next).and_then(|tile| cost(&next, tile)) {
+                Some(step_cost) => step_cost,
+                None => continue,
+            };
+            let next_cost = cost_so_far + step_cost;
+            if best.get(&next).map_or(true, |best| next_cost < *best) {
+                best.insert(next, next_cost);
+                came_from.insert(next, pos);
+                open.push(Reverse((
+                    next_cost + heuristic(&next, to),
+                    next_cost,
+                    next.y,
+                    next.x,
+                )));
+            }
+        }
+    }
+
+    None
+}
+
+/// The default cost of walking onto a tile based on its terrain alone.
+pub fn terrain_cost(terrain: Terrain) -> Option<u32> {
+    use Terrain::*;
+    match terrain {
+        Floor | OpenDoor | StairsDown | StairsUp => Some(1),
+        Rubble => Some(2),
+        // Opening the door takes a turn before it can be walked through.
+        ClosedDoor => Some(2),
+        Wall | Water => None,
+    }
+}
+
+fn heuristic(from: &WorldPosition, to: &WorldPosition) -> u32 {
+    from.distance(to) as u32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn walk(
+        map: &WorldMap,
+        from: &WorldPosition,
+        to: &WorldPosition,
+    ) -> Option<Vec<WorldPosition>> {
+        find_path(map, from, to, |_, tile| terrain_cost(tile.terrain))
+    }
+
+    #[test]
+    fn path_goes_around_walls() {
+        let map = WorldMap::from_rows(&["..#..", "..#..", "....."]);
+        let from = WorldPosition::new(0, 0);
+        let to = WorldPosition::new(4, 0);
+        let path = walk(&map, &from, &to).expect("No path");
+
+        assert_eq!(path.len(), 4);
+        assert_eq!(path.last(), Some(&to));
+        assert!(!path.contains(&from));
+        let mut previous = from;
+        for pos in &path {
+            assert!(previous.is_adjacent(pos));
+            assert_eq!(map.terrain(pos), Some(Terrain::Floor));
+            previous = *pos;
+        }
+    }
+
+    #[test]
+    fn path_to_a_neighbour_is_a_single_step() {
+        let map = WorldMap::from_rows(&["..."]);
+        let to = WorldPosition::new(1, 0);
+        assert_eq!(walk(&map, &WorldPosition::new(0, 0), &to), Some(vec![to]));
+    }
+
+    #[test]
+    fn no_path_to_unreachable_targets() {
+        let map = WorldMap::from_rows(&["..#..", "..~..", "..#.."]);
+        let from = WorldPosition::new(0, 1);
+        assert_eq!(walk(&map, &from, &WorldPosition::new(4, 1)), None);
+        assert_eq!(walk(&map, &from, &WorldPosition::new(9, 9)), None);
+    }
+
+    #[test]
+    fn path_prefers_cheaper_tiles() {
+        let map = WorldMap::from_rows(&[".:::.", "....."]);
+        let path = walk(&map, &WorldPosition::new(0, 0), &WorldPosition::new(4, 0)).unwrap();
+        assert!(path
+            .iter()
+            .all(|pos| map.terrain(pos) != Some(Terrain::Rubble)));
+    }
+}
@@ -1,109 +1,60 @@
-use amethyst::{core::transform::Parent, ecs::Entity, prelude::*};
+use std::{cell::RefCell, rc::Rc};
+
+use amethyst::{
+    core::transform::Parent,
+    ecs::{Dispatcher, Entity},
+    prelude::*,
+};
 
 use crate::{
     components::*,
     data::*,
+    dungeon, mapgen,
+    options::Options,
+    replay,
     resources::*,
+    save::{self, SAVE_PATH},
+    spawner,
+    templates::Templates,
     tui::{
         border::Border,
-        centering::Centered,
         components::*,
         stacking::{StackingContext, StackingRule},
+        style::{Color, Style},
+        viewport::Viewport,
         visibility_relation::VisibleIfChildIs,
     },
     CustomGameData,
 };
 
-pub struct PlayState;
+/// Errors that can't be shown while curses owns the terminal, printed by
+/// `main` once the game is over.
+pub type ExitErrors = Rc<RefCell<Vec<String>>>;
+
+pub struct PlayState {
+    options: Options,
+    errors: ExitErrors,
+}
+
+impl PlayState {
+    pub fn new(options: Options, errors: ExitErrors) -> Self {
+        PlayState { options, errors }
+    }
+}
 
 impl<'a, 'b> State<CustomGameData<'a, 'b>, StateEvent> for PlayState {
     fn on_start(&mut self, data: StateData<CustomGameData>) {
         let world = data.world;
-        world.register::<Character>();
-
-        world.add_resource(WorldMap::new(20, 20));
-
-        let stack = world
-            .create_entity()
-            .with(StackingContext::horizontal())
-            .with(Position::new(0, 0))
-            .build();
-
-        let board_container = world
-            .create_entity()
-            .with(Parent { entity: stack })
-            .with(StackingRule::new().min_width(15).min_height(15))
-            .build();
-
-        let board = world
-            .create_entity()
-            .with(Parent {
-                entity: board_container,
-            })
-            .with(Centered::new(true, true))
-            .with(TextBlock::new((0..10).map(|_| ".".repeat(10)), 10, 10))
-            .build();
-
-        let rhs = world
-            .create_entity()
-            .with(Parent { entity: stack })
-            .with(StackingRule::new().max_width(80).min_width(50).flex(2))
-            .with(StackingContext::vertical())
-            .build();
-
-        let inventory_border = world
-            .create_entity()
-            .with(Parent { entity: rhs })
-            .with(StackingRule::new())
-            .with(Border::new().left())
-            .build();
-
-        world
-            .create_entity()
-            .with(Parent {
-                entity: inventory_border,
-            })
-            .with(Position::new(2, 0))
-            .with(InventoryDisplay::new(InventoryDisplayKind::Own))
-            .build();
-
-        let ground_border = world
-            .create_entity()
-            .with(Parent { entity: rhs })
-            .with(StackingRule::new())
-            .with(Border::new().top().left())
-            .with(VisibleIfChildIs)
-            .build();
-
-        world
-            .create_entity()
-            .with(Parent {
-                entity: ground_border,
-            })
-            .with(Position::new(2, 1))
-            .with(InventoryDisplay::new(InventoryDisplayKind::Ground))
-            .build();
-
-        let log_border = world
-            .create_entity()
-            .with(Parent { entity: rhs })
-            .with(StackingRule::new())
-            .with(Border::new().top().left())
-            .build();
-
-        world
-            .create_entity()
-            .with(Parent { entity: log_border })
-            .with(Position::new(2, 1))
-            .with(LogDisplay)
-            .build();
-
-        world.add_resource(Board(Some(board)));
-
-        initialise_player(world);
-        initialise_enemy(world);
+        // A recording has to start from a fresh game to be replayable.
+        let resume = self.options.record.is_none();
+        initialise_game(world, self.options.seed, resume);
+        if self.options.record.is_some() {
+            let seed = world.read_resource::<GameRng>().seed();
+            world.add_resource(InputRecorder::new(seed));
+        }
 
         data.data.tick_dispatcher.dispatch(&world.res);
+        world.maintain();
     }
 
     fn handle_event(
@@ -120,28 +71,232 @@ impl<'a, 'b> State<CustomGameData<'a, 'b>, StateEvent> for PlayState {
     ) -> Trans<CustomGameData<'a, 'b>, StateEvent> {
         data.world.write_resource::<PlayerActionResource>().action = None;
         data.data.live_dispatcher.dispatch(&data.world.res);
-        if let Some(act) = &data.world.read_resource::<PlayerActionResource>().action {
-            if *act == PlayerAction::Quit {
+        let action = data.world.read_resource::<PlayerActionResource>().action;
+        if let Some(act) = action {
+            if act == PlayerAction::Quit {
+                if let Some(path) = &self.options.record {
+                    // Recorded sessions never touch the save file.
+                    if let Err(err) = replay::save_recording(data.world, path) {
+                        self.errors.borrow_mut().push(format!(
+                            "Could not write the recording to {:?}: {}",
+                            path, err
+                        ));
+                    }
+                    return Trans::Pop;
+                }
+                // Dying is permanent, so a dead player's save is thrown away.
+                let result = if player_alive(data.world) {
+                    save::save(data.world, SAVE_PATH)
+                } else {
+                    save::delete(SAVE_PATH)
+                };
+                if let Err(err) = result {
+                    self.errors
+                        .borrow_mut()
+                        .push(format!("Could not save the game to {}: {}", SAVE_PATH, err));
+                }
                 return Trans::Pop;
             }
-            data.data.tick_dispatcher.dispatch(&data.world.res);
+            run_turn(data.world, &mut data.data.tick_dispatcher);
         }
         Trans::None
     }
 }
 
+/// Builds the UI and either resumes the saved game or starts a new one.
+/// `seed` is only used for new games.
+pub fn initialise_game(world: &mut World, seed: Option<u64>, resume: bool) {
+    world.register::<Character>();
+    world.add_resource(Templates::load().expect("Failed to load templates"));
+
+    let saved_game = if !resume {
+        None
+    } else {
+        match save::load(SAVE_PATH) {
+            Ok(saved_game) => saved_game,
+            Err(err) => {
+                world
+                    .read_resource::<LogEvents>()
+                    .send(format!("Could not load {}: {}", SAVE_PATH, err));
+                None
+            }
+        }
+    };
+
+    let map = match &saved_game {
+        Some(saved_game) => {
+            world.add_resource(GameRng::new(saved_game.seed));
+            saved_game.map.to_map()
+        }
+        None => {
+            let seed = seed.unwrap_or_else(rand::random);
+            let mut rng = GameRng::new(seed);
+            let map = dungeon::generate_level(0, &mut rng);
+            let factions = Factions::new(&world.read_resource::<Templates>().factions);
+            world.add_resource(rng);
+            world.add_resource(Dungeon::default());
+            world.add_resource(factions);
+            world
+                .read_resource::<LogEvents>()
+                .send(format!("New game with seed {}", seed));
+            map
+        }
+    };
+    world.add_resource(map);
+
+    let border_style = Style::new().fg(Color::Blue);
+    let stack = world
+        .create_entity()
+        .with(StackingContext::horizontal())
+        .with(Position::new(0, 0))
+        .build();
+
+    let board_container = world
+        .create_entity()
+        .with(Parent { entity: stack })
+        .with(StackingRule::new().min_width(22).min_height(15))
+        .build();
+
+    let board = world
+        .create_entity()
+        .with(Parent {
+            entity: board_container,
+        })
+        .with(Position::new(0, 0))
+        .with(TextBlock::empty(0, 0))
+        .with(BoardDisplay)
+        .build();
+
+    let rhs = world
+        .create_entity()
+        .with(Parent { entity: stack })
+        .with(StackingRule::new().max_width(80).min_width(50).flex(2))
+        .with(StackingContext::vertical())
+        .build();
+
+    let inventory_border = world
+        .create_entity()
+        .with(Parent { entity: rhs })
+        .with(StackingRule::new())
+        .with(Border::new().left().style(border_style))
+        .build();
+
+    world
+        .create_entity()
+        .with(Parent {
+            entity: inventory_border,
+        })
+        .with(Position::new(2, 0))
+        .with(InventoryDisplay::new(InventoryDisplayKind::Own))
+        .with(Viewport::new(2))
+        .build();
+
+    let ground_border = world
+        .create_entity()
+        .with(Parent { entity: rhs })
+        .with(StackingRule::new())
+        .with(Border::new().top().left().style(border_style))
+        .with(VisibleIfChildIs)
+        .build();
+
+    world
+        .create_entity()
+        .with(Parent {
+            entity: ground_border,
+        })
+        .with(Position::new(2, 1))
+        .with(InventoryDisplay::new(InventoryDisplayKind::Ground))
+        .with(Viewport::new(2))
+        .build();
+
+    let log_border = world
+        .create_entity()
+        .with(Parent { entity: rhs })
+        .with(StackingRule::new())
+        .with(Border::new().top().left().style(border_style))
+        .build();
+
+    world
+        .create_entity()
+        .with(Parent { entity: log_border })
+        .with(Position::new(2, 1))
+        .with(LogDisplay)
+        .with(Viewport::new(1))
+        .build();
+
+    world.add_resource(Board(Some(board)));
+
+    if let Some(saved_game) = saved_game {
+        saved_game.restore(world);
+    } else {
+        initialise_player(world);
+        initialise_enemy(world);
+    }
+}
+
+/// Runs the tick dispatcher with the player's action, then until it's the
+/// player's turn again.
+pub fn run_turn(world: &mut World, dispatcher: &mut Dispatcher) {
+    loop {
+        dispatcher.dispatch(&world.res);
+        world.maintain();
+
+        let player = world.read_resource::<PlayerEntity>().0;
+        let (acted, stalled) = {
+            let scheduler = world.read_resource::<Scheduler>();
+            let acted = player.map_or(true, |player| scheduler.is_acting(player));
+            (acted, scheduler.acting.is_empty())
+        };
+        let mut action = world.write_resource::<PlayerActionResource>();
+        if acted {
+            action.action = None;
+        }
+        let waiting = action.action.is_none()
+            && player.map_or(true, |player| {
+                world
+                    .read_storage::<Actor>()
+                    .get(player)
+                    .map_or(true, Actor::is_ready)
+            });
+        // Nobody acting means nobody ever will, e.g. when everything left
+        // has a speed of 0.
+        if waiting || stalled || !player_alive(world) {
+            break;
+        }
+    }
+}
+
+fn player_alive(world: &World) -> bool {
+    match world.read_resource::<PlayerEntity>().0 {
+        Some(player) => {
+            world.entities().is_alive(player) && !world.read_storage::<Dead>().contains(player)
+        }
+        None => false,
+    }
+}
+
 fn initialise_player(world: &mut World) {
     let board = world.read_resource::<Board>().0.unwrap();
+    let position = mapgen::find_spawn(
+        &world.read_resource::<WorldMap>(),
+        &mut *world.write_resource::<GameRng>(),
+        None,
+    )
+    .expect("Generated level has no room for the player");
     let entity = world
         .create_entity()
         .with(Character)
-        .with(WorldPosition::new(1, 1))
+        .with(position)
         .with(Parent { entity: board })
         .with(PlayerControlledCharacter)
         .with(Team(0))
         .with(Health::new(10))
+        .with(Actor {
+            speed: NORMAL_SPEED,
+            energy: ACTION_ENERGY,
+        })
         .with(Position::default())
-        .with(TextBlock::single_row("@"))
+        .with(TextBlock::single_row("@").with_style(Style::new().fg(Color::Yellow).bold()))
         .with(Named::new("Player"))
         .with(Blink::new(0.5))
         .with(ZLevel::new(1))
@@ -149,6 +304,7 @@ fn initialise_player(world: &mut World) {
             ItemPart::Name("Test weapon".into()),
             ItemPart::Damage(-1),
         ])]))
+        .with(Equipment::default())
         .build();
 
     // Character + WprldPosition: recognized as a character in the world
@@ -156,32 +312,33 @@ fn initialise_player(world: &mut World) {
     // PlayerControlledCharacter: reads player input
     // Parent + Position + TextBlock: recognized as a renderable thing
     // Inventory: has items, can pick up items, drops them when dead
+    // Equipment: worn items, the only ones that count in combat
     // etc
 
     world.add_resource(PlayerEntity(Some(entity)));
 }
 
 pub fn initialise_enemy(world: &mut World) {
-    use rand::Rng;
-    let board = world.read_resource::<Board>().0.unwrap();
-    let mut rng = rand::thread_rng();
-    world
-        .create_entity()
-        .with(Character)
-        .with(WorldPosition::new(8, 8))
-        .with(Parent { entity: board })
-        .with(Team(1))
-        .with(AggressiveAI::new(&[0]))
-        .with(Health::new(5))
-        .with(Position::default())
-        .with(TextBlock::single_row("c"))
-        .with(ZLevel::new(1))
-        .with(Inventory::new(vec![Item::new(vec![
-            ItemPart::Name(["Wooden nail", "Shoe", "???"][rng.gen_range(0, 3)].into()),
-            ItemPart::Damage(rng.gen_range(0, 5)),
-        ])]))
-        .with(Named::new("Enemy"))
-        .build();
+    let player_position = world
+        .read_resource::<PlayerEntity>()
+        .0
+        .and_then(|player| world.read_storage::<WorldPosition>().get(player).cloned());
+    let spawn = {
+        let mut rng = world.write_resource::<GameRng>();
+        let position = mapgen::find_spawn(
+            &world.read_resource::<WorldMap>(),
+            &mut *rng,
+            player_position.as_ref(),
+        );
+        let id = world
+            .read_resource::<Templates>()
+            .random_monster(&mut *rng)
+            .map(|template| template.id.clone());
+        position.and_then(|position| id.map(|id| (position, id)))
+    };
+    if let Some((position, id)) = spawn {
+        spawner::spawn_monster(world, &id, position);
+    }
 }
 
 pub fn initialise_item<T: Builder>(
@@ -193,7 +350,44 @@ pub fn initialise_item<T: Builder>(
     builder
         .with(position)
         .with(Parent { entity: board })
-        .with(Position::new(position.x, position.y))
-        .with(TextBlock::single_row("*"))
+        .with(Position::default())
+        .with(TextBlock::single_row("*").with_style(Style::new().fg(Color::Cyan)))
         .with(item)
 }
+
+#[cfg(test)]
+mod tests {
+    use amethyst::ecs::DispatcherBuilder;
+
+    use super::*;
+    use crate::systems::SchedulerSystem;
+
+    #[test]
+    fn run_turn_stops_when_nobody_can_act() {
+        let mut world = World::new();
+        let mut dispatcher = DispatcherBuilder::new()
+            .with(SchedulerSystem, "scheduler", &[])
+            .build();
+        dispatcher.setup(&mut world.res);
+
+        let player = world
+            .create_entity()
+            .with(Actor::new(0))
+            .with(PlayerControlledCharacter)
+            .build();
+        world.create_entity().with(Actor::new(0)).build();
+        world.add_resource(PlayerEntity(Some(player)));
+        world.write_resource::<PlayerActionResource>().action = Some(PlayerAction::Wait);
+
+        // Would loop forever if the stall went unnoticed.
+        run_turn(&mut world, &mut dispatcher);
+
+        let scheduler = world.read_resource::<Scheduler>();
+        assert!(scheduler.acting.is_empty());
+        assert_eq!(scheduler.time, 0);
+        assert_eq!(
+            world.read_resource::<PlayerActionResource>().action,
+            Some(PlayerAction::Wait)
+        );
+    }
+}
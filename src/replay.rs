@@ -0,0 +1,206 @@
+use std::{fs, path::Path};
+
+use amethyst::{
+    core::{bundle::SystemBundle, shrev::EventChannel, timing::Time, transform::Parent},
+    ecs::DispatcherBuilder,
+    prelude::*,
+};
+use ron::ser::PrettyConfig;
+
+use crate::{
+    bundles::TickBundle,
+    components::*,
+    data::PlayerAction,
+    play,
+    resources::{InputRecorder, PlayerActionResource},
+    save::{SaveError, SaveGame},
+    systems::DetectPlayerActionSystem,
+    tui::{
+        border::Border,
+        centering::Centered,
+        stacking::{StackingContext, StackingRule},
+        Blink, Key, Position, TextBlock, Viewport, VisibleIfChildIs, ZLevel,
+    },
+};
+
+/// Everything needed to play a session back: the seed of the new game and
+/// the input of every frame in which something happened.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct Recording {
+    pub seed: u64,
+    pub frames: Vec<RecordedFrame>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RecordedFrame {
+    pub frame: u64,
+    pub keys: Vec<RecordedKey>,
+    pub action: Option<PlayerAction>,
+}
+
+/// The keys the game reacts to, in a form that can be written to a
+/// recording.
+#[derive(Debug, Copy, Clone, PartialEq, Serialize, Deserialize)]
+pub enum RecordedKey {
+    Character(char),
+    PageUp,
+    PageDown,
+}
+
+impl RecordedKey {
+    pub fn from_key(key: &Key) -> Option<Self> {
+        match key {
+            Key::Character(c) => Some(RecordedKey::Character(*c)),
+            Key::KeyPPage => Some(RecordedKey::PageUp),
+            Key::KeyNPage => Some(RecordedKey::PageDown),
+            _ => None,
+        }
+    }
+
+    pub fn to_key(self) -> Key {
+        match self {
+            RecordedKey::Character(c) => Key::Character(c),
+            RecordedKey::PageUp => Key::KeyPPage,
+            RecordedKey::PageDown => Key::KeyNPage,
+        }
+    }
+}
+
+pub fn save_recording<P: AsRef<Path>>(world: &World, path: P) -> Result<(), SaveError> {
+    let recorder = world.read_resource::<InputRecorder>();
+    fs::write(
+        path,
+        ron::ser::to_string_pretty(&recorder.recording, PrettyConfig::default())?,
+    )?;
+    Ok(())
+}
+
+pub fn load_recording<P: AsRef<Path>>(path: P) -> Result<Recording, SaveError> {
+    Ok(ron::de::from_str(&fs::read_to_string(path)?)?)
+}
+
+/// Plays a recording back without a terminal and returns the final state
+/// of the world in the save game format.
+pub fn run<P: AsRef<Path>>(path: P) -> Result<String, SaveError> {
+    let recording = load_recording(path)?;
+
+    let mut world = World::new();
+    world.add_resource(Time::default());
+    register_ui_components(&mut world);
+
+    let mut input_dispatcher = DispatcherBuilder::new()
+        .with(
+            DetectPlayerActionSystem::default(),
+            "detect_player_action",
+            &[],
+        )
+        .build();
+    input_dispatcher.setup(&mut world.res);
+
+    let mut tick_builder = DispatcherBuilder::new();
+    TickBundle::default()
+        .build(&mut tick_builder)
+        .expect("Failed to build the tick dispatcher");
+    let mut tick_dispatcher = tick_builder.build();
+    tick_dispatcher.setup(&mut world.res);
+
+    play::initialise_game(&mut world, Some(recording.seed), false);
+    tick_dispatcher.dispatch(&world.res);
+    world.maintain();
+
+    for frame in recording.frames {
+        world
+            .write_resource::<EventChannel<Key>>()
+            .iter_write(frame.keys.into_iter().map(RecordedKey::to_key));
+        world.write_resource::<PlayerActionResource>().action = None;
+        input_dispatcher.dispatch(&world.res);
+
+        // Held keys only turn into actions once `hold_delay` has passed,
+        // which depends on frame timing, so the recorded action wins.
+        world.write_resource::<PlayerActionResource>().action = frame.action;
+        match frame.action {
+            Some(PlayerAction::Quit) => break,
+            Some(_) => play::run_turn(&mut world, &mut tick_dispatcher),
+            None => world.maintain(),
+        }
+    }
+
+    Ok(ron::ser::to_string_pretty(
+        &SaveGame::capture(&world),
+        PrettyConfig::default(),
+    )?)
+}
+
+/// Without the `TuiBundle` nothing registers the storages the UI entities
+/// are built from.
+fn register_ui_components(world: &mut World) {
+    world.register::<Parent>();
+    world.register::<Position>();
+    world.register::<TextBlock>();
+    world.register::<ZLevel>();
+    world.register::<Blink>();
+    world.register::<StackingContext>();
+    world.register::<StackingRule>();
+    world.register::<Centered>();
+    world.register::<Border>();
+    world.register::<VisibleIfChildIs>();
+    world.register::<Viewport>();
+    world.register::<LogDisplay>();
+    world.register::<BoardDisplay>();
+    world.register::<Named>();
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+
+    use super::*;
+    use crate::data::Direction;
+
+    #[test]
+    fn recorded_keys_survive_the_round_trip() {
+        for key in &[
+            RecordedKey::Character('>'),
+            RecordedKey::PageUp,
+            RecordedKey::PageDown,
+        ] {
+            assert_eq!(RecordedKey::from_key(&key.to_key()), Some(*key));
+        }
+        assert_eq!(RecordedKey::from_key(&Key::KeyHome), None);
+    }
+
+    #[test]
+    fn replays_are_deterministic() {
+        let actions = Direction::ALL
+            .iter()
+            .cycle()
+            .take(24)
+            .map(|dir| PlayerAction::Move(*dir))
+            .chain(vec![PlayerAction::Wait; 6]);
+        let recording = Recording {
+            seed: 42,
+            frames: actions
+                .enumerate()
+                .map(|(frame, action)| RecordedFrame {
+                    frame: frame as u64,
+                    keys: Vec::new(),
+                    action: Some(action),
+                })
+                .collect(),
+        };
+        let path = env::temp_dir().join(format!("replay-test-{}.ron", std::process::id()));
+        fs::write(
+            &path,
+            ron::ser::to_string_pretty(&recording, PrettyConfig::default()).unwrap(),
+        )
+        .unwrap();
+
+        let first = run(&path);
+        let second = run(&path);
+        fs::remove_file(&path).unwrap();
+
+        let first = first.unwrap();
+        assert_eq!(first, second.unwrap());
+        assert!(first.contains("seed: 42"));
+    }
+}
@@ -1,9 +1,17 @@
-use amethyst::ecs::prelude::*;
+use std::collections::BTreeMap;
+
+use amethyst::{core::shrev::EventChannel, ecs::prelude::*};
 use crossbeam_channel as channel;
+use rand::{rngs::StdRng, RngCore, SeedableRng};
 
 use crate::{
-    components::{Item, WorldPosition},
-    data::{Attack, Direction, PlayerAction},
+    components::{Item, WorldPosition, ACTION_ENERGY, NORMAL_SPEED},
+    data::{Action, PlayerAction, Relation, Terrain, TileVisibility},
+    mapgen::Room,
+    replay::{RecordedFrame, RecordedKey, Recording},
+    save::SavedLevel,
+    templates::FactionTemplate,
+    tui::{Position, Style},
 };
 
 #[derive(Default, Debug, Clone)]
@@ -46,12 +54,60 @@ impl<D> MpscChannel<D> {
     }
 }
 
-pub type MovementActions = MpscChannel<(Entity, Direction)>;
-pub type AttackActions = MpscChannel<Attack>;
+/// An action and the character doing it.
+#[derive(Debug, Clone)]
+pub struct ActionRequest {
+    pub actor: Entity,
+    pub action: Action,
+}
+
+/// Where player input and AIs submit actions for `ResolveActionsSystem`.
+pub type ActionQueue = MpscChannel<ActionRequest>;
 
 #[derive(Default, Debug, Clone)]
 pub struct PlayerEntity(pub Option<Entity>);
 
+/// The part of the map shown on the board, which is as big as its panel.
+/// `x` and `y` are the world coordinates of the board's top left corner and
+/// are negative when the map is smaller than the board, to center it.
+#[derive(Default, Debug, Copy, Clone, PartialEq)]
+pub struct Camera {
+    pub x: i32,
+    pub y: i32,
+    pub width: i32,
+    pub height: i32,
+}
+
+impl Camera {
+    /// Centers the view on `focus`, without showing more than necessary
+    /// of what lies outside a `map_width` by `map_height` map.
+    pub fn follow(&mut self, focus: &WorldPosition, map_width: i32, map_height: i32) {
+        fn axis(focus: i32, view: i32, map: i32) -> i32 {
+            if map <= view {
+                -(view - map) / 2
+            } else {
+                (focus - view / 2).max(0).min(map - view)
+            }
+        }
+        self.x = axis(focus.x, self.width, map_width);
+        self.y = axis(focus.y, self.height, map_height);
+    }
+
+    /// Where `pos` is on the board, if it's in view.
+    pub fn to_board(&self, pos: &WorldPosition) -> Option<Position> {
+        let (x, y) = (pos.x - self.x, pos.y - self.y);
+        if x >= 0 && x < self.width && y >= 0 && y < self.height {
+            Some(Position::new(x, y))
+        } else {
+            None
+        }
+    }
+
+    pub fn to_world(&self, board: Position) -> WorldPosition {
+        WorldPosition::new(board.x + self.x, board.y + self.y)
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct WorldItem {
     pub entity: Entity,
@@ -60,6 +116,8 @@ pub struct WorldItem {
 
 #[derive(Default, Debug, Clone)]
 pub struct WorldTile {
+    pub terrain: Terrain,
+    pub visibility: TileVisibility,
     pub character: Option<Entity>,
     pub items: Vec<WorldItem>,
 }
@@ -69,6 +127,8 @@ pub struct WorldMap {
     pub width: usize,
     pub height: usize,
     pub tiles: Vec<Vec<WorldTile>>,
+    /// Areas suitable for spawning things, filled in by the level generator.
+    pub rooms: Vec<Room>,
 }
 
 impl WorldMap {
@@ -86,6 +146,7 @@ impl WorldMap {
                         .collect()
                 })
                 .collect(),
+            rooms: Vec::new(),
         }
     }
 
@@ -125,21 +186,90 @@ impl WorldMap {
     pub fn is_legal_pos(&self, pos: &WorldPosition) -> bool {
         pos.x >= 0 && pos.x < self.width as i32 && pos.y >= 0 && pos.y < self.height as i32
     }
+
+    pub fn terrain(&self, pos: &WorldPosition) -> Option<Terrain> {
+        self.get(pos).map(|tile| tile.terrain)
+    }
+
+    pub fn is_visible(&self, pos: &WorldPosition) -> bool {
+        self.get(pos)
+            .map_or(false, |tile| tile.visibility == TileVisibility::Visible)
+    }
+
+    pub fn set_terrain(&mut self, pos: &WorldPosition, terrain: Terrain) {
+        if let Some(tile) = self.get_mut(pos) {
+            tile.terrain = terrain;
+        }
+    }
+
+    /// Builds a map from rows of terrain glyphs, for tests.
+    #[cfg(test)]
+    pub fn from_rows(rows: &[&str]) -> Self {
+        use Terrain::*;
+        let width = rows.first().map_or(0, |row| row.chars().count());
+        let mut map = WorldMap::new(width, rows.len());
+        for (y, row) in rows.iter().enumerate() {
+            for (x, glyph) in row.chars().enumerate() {
+                let terrain = [
+                    Floor, Wall, ClosedDoor, OpenDoor, Water, Rubble, StairsDown, StairsUp,
+                ]
+                .iter()
+                .cloned()
+                .find(|terrain| terrain.glyph() == glyph)
+                .unwrap_or_else(|| panic!("Unknown terrain glyph {:?}", glyph));
+                map.set_terrain(&WorldPosition::new(x as i32, y as i32), terrain);
+            }
+        }
+        map
+    }
+}
+
+/// Something on a tile changed: a character or item entered or left it,
+/// or its terrain was altered.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct TileChanged {
+    pub pos: WorldPosition,
+    pub cause: Option<Entity>,
+}
+
+pub type TileChanges = EventChannel<TileChanged>;
+
+/// A character did something that can be heard up to `radius` steps away.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Noise {
+    pub pos: WorldPosition,
+    pub radius: i32,
+    pub source: Entity,
 }
 
+pub type Noises = EventChannel<Noise>;
+
 #[derive(Default, Debug, Clone)]
 pub struct EventLog {
-    pub events: Vec<String>,
+    pub events: Vec<LogLine>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LogLine {
+    pub text: String,
+    pub style: Style,
 }
 
-pub struct LogLine(pub String);
+impl LogLine {
+    pub fn styled<T: Into<String>>(text: T, style: Style) -> Self {
+        LogLine {
+            text: text.into(),
+            style,
+        }
+    }
+}
 
 impl<T> From<T> for LogLine
 where
     T: Into<String>,
 {
     fn from(value: T) -> LogLine {
-        LogLine(value.into())
+        LogLine::styled(value, Style::default())
     }
 }
 
@@ -148,5 +278,190 @@ pub type LogEvents = MpscChannel<LogLine>;
 #[derive(Default)]
 pub struct Board(pub Option<Entity>);
 
+/// Ticks it takes an actor of `NORMAL_SPEED` to gain `ACTION_ENERGY`.
+pub const TURN_LENGTH: u64 = (ACTION_ENERGY / NORMAL_SPEED) as u64;
+
+/// Game time, advanced by `SchedulerSystem`. Every dispatch of the tick
+/// dispatcher is one step, in which the actors that are ready act.
+#[derive(Default, Debug, Clone, Serialize, Deserialize)]
+pub struct Scheduler {
+    /// Ticks since the start of the game.
+    pub time: u64,
+    /// Steps since the start of the game, the random generator is reseeded
+    /// from it.
+    pub step: u64,
+    /// Actors that act in the current step.
+    #[serde(skip)]
+    pub acting: Vec<Entity>,
+}
+
+impl Scheduler {
+    /// The time in turns of a normal speed actor.
+    pub fn turn(&self) -> u64 {
+        self.time / TURN_LENGTH
+    }
+
+    pub fn is_acting(&self, entity: Entity) -> bool {
+        self.acting.contains(&entity)
+    }
+}
+
+/// How the teams get along with each other. Relations go both ways and
+/// change during the game, e.g. attacking a neutral team turns it hostile.
+#[derive(Default, Debug, Clone, Serialize, Deserialize)]
+pub struct Factions {
+    names: BTreeMap<u32, String>,
+    /// Keyed by the lower team id first. Missing pairs are neutral.
+    relations: BTreeMap<(u32, u32), Relation>,
+}
+
+impl Factions {
+    pub fn new(templates: &[FactionTemplate]) -> Self {
+        let mut factions = Factions::default();
+        for template in templates {
+            factions.names.insert(template.id, template.name.clone());
+            for &other in &template.hostile {
+                factions.set(template.id, other, Relation::Hostile);
+            }
+            for &other in &template.allied {
+                factions.set(template.id, other, Relation::Allied);
+            }
+        }
+        factions
+    }
+
+    pub fn name(&self, team: u32) -> String {
+        self.names
+            .get(&team)
+            .cloned()
+            .unwrap_or_else(|| format!("team {}", team))
+    }
+
+    /// A team is always allied with itself.
+    pub fn relation(&self, a: u32, b: u32) -> Relation {
+        if a == b {
+            return Relation::Allied;
+        }
+        self.relations
+            .get(&(a.min(b), a.max(b)))
+            .cloned()
+            .unwrap_or(Relation::Neutral)
+    }
+
+    pub fn set(&mut self, a: u32, b: u32, relation: Relation) {
+        if a != b {
+            self.relations.insert((a.min(b), a.max(b)), relation);
+        }
+    }
+
+    pub fn is_hostile(&self, a: u32, b: u32) -> bool {
+        self.relation(a, b) == Relation::Hostile
+    }
+}
+
+/// The stack of levels. Only the current one lives in the world, the
+/// others are kept frozen until the player comes back.
 #[derive(Default)]
-pub struct TurnCounter(pub i32);
+pub struct Dungeon {
+    /// Current depth, 0 is the top level.
+    pub depth: usize,
+    /// Frozen levels, indexed by depth. `None` for the current level and
+    /// for levels that haven't been generated yet.
+    pub levels: Vec<Option<SavedLevel>>,
+}
+
+impl Dungeon {
+    pub fn freeze(&mut self, depth: usize, level: SavedLevel) {
+        if self.levels.len() <= depth {
+            self.levels.resize_with(depth + 1, || None);
+        }
+        self.levels[depth] = Some(level);
+    }
+
+    pub fn thaw(&mut self, depth: usize) -> Option<SavedLevel> {
+        self.levels.get_mut(depth).and_then(Option::take)
+    }
+}
+
+/// The only source of randomness in the game.
+///
+/// The generator is reseeded from the game seed at the start of every turn,
+/// so the state at any turn only depends on the seed and the turn number.
+/// This keeps runs replayable and saves small.
+#[derive(Debug, Clone)]
+pub struct GameRng {
+    seed: u64,
+    rng: StdRng,
+}
+
+impl Default for GameRng {
+    fn default() -> Self {
+        GameRng::new(0)
+    }
+}
+
+impl GameRng {
+    pub fn new(seed: u64) -> Self {
+        GameRng {
+            seed,
+            rng: StdRng::seed_from_u64(seed),
+        }
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    pub fn reseed_for_step(&mut self, step: u64) {
+        let mixed = self.seed ^ step.wrapping_mul(0x9E37_79B9_7F4A_7C15);
+        self.rng = StdRng::seed_from_u64(mixed);
+    }
+}
+
+impl RngCore for GameRng {
+    fn next_u32(&mut self) -> u32 {
+        self.rng.next_u32()
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        self.rng.next_u64()
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        self.rng.fill_bytes(dest)
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), rand::Error> {
+        self.rng.try_fill_bytes(dest)
+    }
+}
+
+/// Collects the input of a session. Only present while `--record` is active.
+pub struct InputRecorder {
+    pub recording: Recording,
+    frame: u64,
+}
+
+impl InputRecorder {
+    pub fn new(seed: u64) -> Self {
+        InputRecorder {
+            recording: Recording {
+                seed,
+                frames: Vec::new(),
+            },
+            frame: 0,
+        }
+    }
+
+    /// Records one frame. Frames without keys or actions are skipped.
+    pub fn record(&mut self, keys: Vec<RecordedKey>, action: Option<PlayerAction>) {
+        self.frame += 1;
+        if !keys.is_empty() || action.is_some() {
+            self.recording.frames.push(RecordedFrame {
+                frame: self.frame,
+                keys,
+                action,
+            });
+        }
+    }
+}
@@ -0,0 +1,505 @@
+use std::{error::Error, fmt, fs, io, path::Path};
+
+use amethyst::{core::transform::Parent, ecs::Entity, prelude::*};
+use ron::ser::PrettyConfig;
+
+use crate::{
+    components::*,
+    data::{EquipSlot, Terrain, TileVisibility},
+    mapgen::Room,
+    play::initialise_item,
+    resources::{
+        Board, Dungeon, EventLog, Factions, GameRng, LogEvents, LogLine, PlayerEntity, Scheduler,
+        WorldMap,
+    },
+    tui::{Blink, Position, Style, TextBlock, ZLevel},
+};
+
+/// Bump this whenever the layout of `SaveGame` changes.
+pub const SAVE_VERSION: u32 = 10;
+pub const SAVE_PATH: &str = "savegame.ron";
+
+#[derive(Debug)]
+pub enum SaveError {
+    Io(io::Error),
+    Serialize(ron::ser::Error),
+    Deserialize(ron::de::Error),
+    Version(u32),
+    /// The save parses, but doesn't describe a game that can be restored.
+    Invalid(String),
+}
+
+impl fmt::Display for SaveError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SaveError::Io(err) => write!(f, "I/O error: {}", err),
+            SaveError::Serialize(err) => write!(f, "Serialization failed: {}", err),
+            SaveError::Deserialize(err) => write!(f, "Corrupt save: {}", err),
+            SaveError::Version(version) => write!(
+                f,
+                "Save version {} is not supported (expected {})",
+                version, SAVE_VERSION
+            ),
+            SaveError::Invalid(reason) => write!(f, "Corrupt save: {}", reason),
+        }
+    }
+}
+
+impl Error for SaveError {}
+
+impl From<io::Error> for SaveError {
+    fn from(err: io::Error) -> Self {
+        SaveError::Io(err)
+    }
+}
+
+impl From<ron::ser::Error> for SaveError {
+    fn from(err: ron::ser::Error) -> Self {
+        SaveError::Serialize(err)
+    }
+}
+
+impl From<ron::de::Error> for SaveError {
+    fn from(err: ron::de::Error) -> Self {
+        SaveError::Deserialize(err)
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct SaveGame {
+    pub version: u32,
+    pub seed: u64,
+    pub scheduler: Scheduler,
+    pub factions: Factions,
+    pub log: Vec<LogLine>,
+    pub depth: usize,
+    pub map: SavedMap,
+    pub entities: Vec<SavedEntity>,
+    /// The other levels of the `Dungeon`.
+    pub levels: Vec<Option<SavedLevel>>,
+}
+
+/// A level the player isn't on.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct SavedLevel {
+    pub map: SavedMap,
+    pub entities: Vec<SavedEntity>,
+}
+
+/// The parts of a `WorldMap` that aren't derived from entities.
+/// Characters and items are put back on their tiles by `ApplyWorldMapSystem`.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct SavedMap {
+    pub width: usize,
+    pub height: usize,
+    pub terrain: Vec<Vec<Terrain>>,
+    pub visibility: Vec<Vec<TileVisibility>>,
+    pub rooms: Vec<Room>,
+}
+
+impl SavedMap {
+    pub fn from_map(map: &WorldMap) -> Self {
+        SavedMap {
+            width: map.width,
+            height: map.height,
+            terrain: map
+                .tiles
+                .iter()
+                .map(|row| row.iter().map(|tile| tile.terrain).collect())
+                .collect(),
+            visibility: map
+                .tiles
+                .iter()
+                .map(|row| row.iter().map(|tile| tile.visibility).collect())
+                .collect(),
+            rooms: map.rooms.clone(),
+        }
+    }
+
+    /// Checks that there are as many rows and columns as the size says,
+    /// which `to_map` relies on.
+    fn validate(&self) -> Result<(), SaveError> {
+        if !has_size(&self.terrain, self.width, self.height)
+            || !has_size(&self.visibility, self.width, self.height)
+        {
+            return Err(SaveError::Invalid(format!(
+                "tiles don't match the map size {}x{}",
+                self.width, self.height
+            )));
+        }
+        Ok(())
+    }
+
+    fn contains(&self, pos: &WorldPosition) -> bool {
+        pos.x >= 0 && pos.y >= 0 && (pos.x as usize) < self.width && (pos.y as usize) < self.height
+    }
+
+    pub fn to_map(&self) -> WorldMap {
+        let mut map = WorldMap::new(self.width, self.height);
+        for (y, row) in map.tiles.iter_mut().enumerate() {
+            for (x, tile) in row.iter_mut().enumerate() {
+                tile.terrain = self.terrain[y][x];
+                tile.visibility = self.visibility[y][x];
+            }
+        }
+        map.rooms = self.rooms.clone();
+        map
+    }
+}
+
+fn has_size<T>(rows: &[Vec<T>], width: usize, height: usize) -> bool {
+    rows.len() == height && rows.iter().all(|row| row.len() == width)
+}
+
+/// A character or an item lying on the ground.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct SavedEntity {
+    pub player: bool,
+    pub character: bool,
+    pub position: WorldPosition,
+    pub glyph: Option<String>,
+    pub style: Style,
+    pub name: Option<String>,
+    pub health: Option<Health>,
+    pub team: Option<Team>,
+    pub inventory: Option<Vec<Item>>,
+    pub equipment: Option<Vec<(EquipSlot, Item)>>,
+    pub item: Option<Item>,
+    pub ai: Option<Ai>,
+    pub stunned: Option<Stunned>,
+    pub actor: Option<Actor>,
+}
+
+/// Every character and item on the current level, along with the entity
+/// it was captured from.
+pub fn capture_entities(world: &World) -> Vec<(Entity, SavedEntity)> {
+    let entities = world.entities();
+    let position = world.read_storage::<WorldPosition>();
+    let dead = world.read_storage::<Dead>();
+    let player = world.read_storage::<PlayerControlledCharacter>();
+    let character = world.read_storage::<Character>();
+    let text_block = world.read_storage::<TextBlock>();
+    let named = world.read_storage::<Named>();
+    let health = world.read_storage::<Health>();
+    let team = world.read_storage::<Team>();
+    let inventory = world.read_storage::<Inventory>();
+    let equipment = world.read_storage::<Equipment>();
+    let item = world.read_storage::<Item>();
+    let ai = world.read_storage::<Ai>();
+    let stunned = world.read_storage::<Stunned>();
+    let actor = world.read_storage::<Actor>();
+
+    (&*entities, &position, !&dead)
+        .join()
+        .filter(|(entity, _, ())| character.contains(*entity) || item.contains(*entity))
+        .map(|(entity, position, ())| {
+            let saved = SavedEntity {
+                player: player.contains(entity),
+                character: character.contains(entity),
+                position: *position,
+                glyph: text_block.get(entity).and_then(|x| x.rows.first().cloned()),
+                style: text_block
+                    .get(entity)
+                    .map_or_else(Style::default, |x| x.style),
+                name: named.get(entity).map(|x| x.name.to_string()),
+                health: health.get(entity).cloned(),
+                team: team.get(entity).cloned(),
+                inventory: inventory.get(entity).map(|x| x.items.clone()),
+                equipment: equipment.get(entity).map(|x| x.items.clone()),
+                item: item.get(entity).cloned(),
+                ai: ai.get(entity).cloned(),
+                stunned: stunned.get(entity).cloned(),
+                actor: actor.get(entity).cloned(),
+            };
+            (entity, saved)
+        })
+        .collect()
+}
+
+impl SaveGame {
+    pub fn capture(world: &World) -> SaveGame {
+        let dungeon = world.read_resource::<Dungeon>();
+        SaveGame {
+            version: SAVE_VERSION,
+            seed: world.read_resource::<GameRng>().seed(),
+            scheduler: world.read_resource::<Scheduler>().clone(),
+            factions: world.read_resource::<Factions>().clone(),
+            log: world.read_resource::<EventLog>().events.clone(),
+            depth: dungeon.depth,
+            map: SavedMap::from_map(&world.read_resource::<WorldMap>()),
+            entities: capture_entities(world)
+                .into_iter()
+                .map(|(_, saved)| saved)
+                .collect(),
+            levels: dungeon.levels.clone(),
+        }
+    }
+
+    /// Recreates the saved entities under the current `Board`.
+    /// The map and `GameRng` have to be added separately.
+    pub fn restore(self, world: &mut World) {
+        let board = world.read_resource::<Board>().0.unwrap();
+
+        for saved in self.entities {
+            let is_player = saved.player;
+            let entity = restore_entity(world, board, saved);
+            if is_player {
+                world.add_resource(PlayerEntity(Some(entity)));
+            }
+        }
+
+        world
+            .write_resource::<GameRng>()
+            .reseed_for_step(self.scheduler.step);
+        world.add_resource(self.scheduler);
+        world.add_resource(self.factions);
+        world.add_resource(Dungeon {
+            depth: self.depth,
+            levels: self.levels,
+        });
+        world.add_resource(EventLog { events: self.log });
+        world.read_resource::<LogEvents>().send("Game loaded");
+    }
+}
+
+pub fn restore_entity(world: &mut World, board: Entity, saved: SavedEntity) -> Entity {
+    if let Some(item) = saved.item {
+        return initialise_item(world.create_entity(), board, saved.position, item).build();
+    }
+
+    let mut builder = world
+        .create_entity()
+        .with(saved.position)
+        .with(Parent { entity: board })
+        .with(Position::default())
+        .with(
+            TextBlock::single_row(saved.glyph.unwrap_or_else(|| "?".to_owned()))
+                .with_style(saved.style),
+        )
+        .with(ZLevel::new(1));
+
+    if saved.character {
+        builder = builder.with(Character);
+    }
+    if saved.player {
+        builder = builder
+            .with(PlayerControlledCharacter)
+            .with(Blink::new(0.5));
+    }
+    if let Some(name) = saved.name {
+        builder = builder.with(Named::new(name));
+    }
+    if let Some(health) = saved.health {
+        builder = builder.with(health);
+    }
+    if let Some(team) = saved.team {
+        builder = builder.with(team);
+    }
+    if let Some(items) = saved.inventory {
+        builder = builder.with(Inventory::new(items));
+    }
+    if let Some(items) = saved.equipment {
+        builder = builder.with(Equipment::new(items));
+    }
+    if let Some(ai) = saved.ai {
+        builder = builder.with(ai);
+    }
+    if let Some(stunned) = saved.stunned {
+        builder = builder.with(stunned);
+    }
+    if let Some(actor) = saved.actor {
+        builder = builder.with(actor);
+    }
+    builder.build()
+}
+
+/// Loads a save, or returns `None` if there isn't one.
+pub fn load<P: AsRef<Path>>(path: P) -> Result<Option<SaveGame>, SaveError> {
+    if !path.as_ref().exists() {
+        return Ok(None);
+    }
+    parse(&fs::read_to_string(path)?).map(Some)
+}
+
+/// Reads a save and checks that it describes a game that can be restored.
+pub fn parse(source: &str) -> Result<SaveGame, SaveError> {
+    let save: SaveGame = ron::de::from_str(source)?;
+    if save.version != SAVE_VERSION {
+        return Err(SaveError::Version(save.version));
+    }
+    validate_level(&save.map, &save.entities)?;
+    for level in save.levels.iter().flatten() {
+        validate_level(&level.map, &level.entities)?;
+    }
+    if !save.entities.iter().any(|x| x.player) {
+        return Err(SaveError::Invalid("there is no player".to_owned()));
+    }
+    Ok(save)
+}
+
+fn validate_level(map: &SavedMap, entities: &[SavedEntity]) -> Result<(), SaveError> {
+    map.validate()?;
+    match entities.iter().find(|x| !map.contains(&x.position)) {
+        Some(outside) => Err(SaveError::Invalid(format!(
+            "{} at {:?} is outside of the map",
+            outside.name.as_ref().map_or("an entity", |x| x.as_str()),
+            outside.position
+        ))),
+        None => Ok(()),
+    }
+}
+
+pub fn save<P: AsRef<Path>>(world: &World, path: P) -> Result<(), SaveError> {
+    let save = SaveGame::capture(world);
+    fs::write(
+        path,
+        ron::ser::to_string_pretty(&save, PrettyConfig::default())?,
+    )?;
+    Ok(())
+}
+
+/// Removes the save, if any. Used when the player dies.
+pub fn delete<P: AsRef<Path>>(path: P) -> Result<(), SaveError> {
+    if path.as_ref().exists() {
+        fs::remove_file(path)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entity(player: bool, position: WorldPosition) -> SavedEntity {
+        SavedEntity {
+            player,
+            character: true,
+            position,
+            glyph: Some("@".to_owned()),
+            style: Style::default(),
+            name: Some(if player { "You" } else { "Rat" }.to_owned()),
+            health: None,
+            team: None,
+            inventory: None,
+            equipment: None,
+            item: None,
+            ai: None,
+            stunned: None,
+            actor: None,
+        }
+    }
+
+    fn save_game(map: SavedMap, entities: Vec<SavedEntity>) -> SaveGame {
+        SaveGame {
+            version: SAVE_VERSION,
+            seed: 7,
+            scheduler: Scheduler::default(),
+            factions: Factions::default(),
+            log: Vec::new(),
+            depth: 0,
+            map,
+            entities,
+            levels: Vec::new(),
+        }
+    }
+
+    fn to_ron(save: &SaveGame) -> String {
+        ron::ser::to_string_pretty(save, PrettyConfig::default()).unwrap()
+    }
+
+    fn is_invalid(save: &SaveGame) -> bool {
+        match parse(&to_ron(save)) {
+            Err(SaveError::Invalid(_)) => true,
+            _ => false,
+        }
+    }
+
+    fn small_map() -> SavedMap {
+        let mut map = WorldMap::new(4, 3);
+        map.set_terrain(&WorldPosition::new(0, 0), Terrain::Wall);
+        map.set_terrain(&WorldPosition::new(3, 2), Terrain::StairsDown);
+        map.tiles[1][2].visibility = TileVisibility::Remembered;
+        map.rooms.push(Room::new(1, 1, 2, 1));
+        SavedMap::from_map(&map)
+    }
+
+    #[test]
+    fn map_survives_the_round_trip() {
+        let saved = small_map();
+        let map = saved.to_map();
+        assert_eq!((map.width, map.height), (4, 3));
+        assert_eq!(map.terrain(&WorldPosition::new(0, 0)), Some(Terrain::Wall));
+        assert_eq!(
+            map.terrain(&WorldPosition::new(3, 2)),
+            Some(Terrain::StairsDown)
+        );
+        assert_eq!(map.tiles[1][2].visibility, TileVisibility::Remembered);
+        assert_eq!(map.tiles[1][1].visibility, TileVisibility::Unseen);
+        assert_eq!(map.rooms, vec![Room::new(1, 1, 2, 1)]);
+    }
+
+    #[test]
+    fn save_survives_the_round_trip() {
+        let save = save_game(
+            small_map(),
+            vec![
+                entity(true, WorldPosition::new(1, 1)),
+                entity(false, WorldPosition::new(2, 1)),
+            ],
+        );
+        let source = to_ron(&save);
+        let loaded = parse(&source).unwrap();
+        assert_eq!(loaded.seed, 7);
+        assert_eq!(loaded.entities.len(), 2);
+        assert_eq!(loaded.entities[1].position, WorldPosition::new(2, 1));
+        assert_eq!(to_ron(&loaded), source);
+    }
+
+    #[test]
+    fn rejects_other_versions() {
+        let mut save = save_game(small_map(), vec![entity(true, WorldPosition::new(1, 1))]);
+        save.version = SAVE_VERSION - 1;
+        match parse(&to_ron(&save)) {
+            Err(SaveError::Version(version)) => assert_eq!(version, SAVE_VERSION - 1),
+            _ => panic!("Expected a version error"),
+        }
+    }
+
+    #[test]
+    fn rejects_truncated_maps() {
+        let mut map = small_map();
+        map.terrain.pop();
+        let save = save_game(map, vec![entity(true, WorldPosition::new(1, 1))]);
+        assert!(is_invalid(&save));
+
+        let mut map = small_map();
+        map.visibility[1].pop();
+        let save = save_game(map, vec![entity(true, WorldPosition::new(1, 1))]);
+        assert!(is_invalid(&save));
+    }
+
+    #[test]
+    fn rejects_entities_outside_the_map() {
+        let save = save_game(
+            small_map(),
+            vec![
+                entity(true, WorldPosition::new(1, 1)),
+                entity(false, WorldPosition::new(4, 1)),
+            ],
+        );
+        assert!(is_invalid(&save));
+
+        let mut save = save_game(small_map(), vec![entity(true, WorldPosition::new(1, 1))]);
+        save.levels.push(Some(SavedLevel {
+            map: small_map(),
+            entities: vec![entity(false, WorldPosition::new(0, -1))],
+        }));
+        assert!(is_invalid(&save));
+    }
+
+    #[test]
+    fn rejects_saves_without_a_player() {
+        let save = save_game(small_map(), vec![entity(false, WorldPosition::new(1, 1))]);
+        assert!(is_invalid(&save));
+    }
+}
@@ -0,0 +1,48 @@
+use amethyst::{core::transform::Parent, ecs::Entity, prelude::*};
+
+use crate::{
+    components::*,
+    resources::{Board, GameRng},
+    templates::Templates,
+    tui::{Position, Style, TextBlock, ZLevel},
+};
+
+/// Builds a monster from its template. Returns `None` for unknown ids.
+pub fn spawn_monster(world: &mut World, id: &str, position: WorldPosition) -> Option<Entity> {
+    let board = world.read_resource::<Board>().0.unwrap();
+    let (template, item) = {
+        let templates = world.read_resource::<Templates>();
+        let template = templates.monster(id)?.clone();
+        let item = templates
+            .random_loot(&template, &mut *world.write_resource::<GameRng>())
+            .map(|x| x.build());
+        (template, item)
+    };
+
+    // Monsters put on whatever they can wear.
+    let (mut carried, mut worn) = (Vec::new(), Vec::new());
+    if let Some(item) = item {
+        match item.properties().slot {
+            Some(slot) => worn.push((slot, item)),
+            None => carried.push(item),
+        }
+    }
+
+    let entity = world
+        .create_entity()
+        .with(Character)
+        .with(position)
+        .with(Parent { entity: board })
+        .with(Team(template.team))
+        .with(Health::new(template.health))
+        .with(Actor::new(template.speed))
+        .with(Position::default())
+        .with(TextBlock::single_row(template.glyph).with_style(Style::new().fg(template.color)))
+        .with(ZLevel::new(1))
+        .with(Inventory::new(carried))
+        .with(Equipment::new(worn))
+        .with(Named::new(template.name))
+        .with(Ai::new(template.behaviours, position))
+        .build();
+    Some(entity)
+}
@@ -1,4 +1,7 @@
-use crate::components;
+use crate::{
+    components,
+    data::{ItemProperties, StatusEffect},
+};
 use amethyst::ecs::{self, prelude::*, shred::ResourceId};
 
 pub trait EventSystem<'a> {
@@ -69,24 +72,45 @@ where
     }
 }
 
+/// A hit on its way through the damage chain.
+pub struct DamageEvent {
+    pub attacker: Entity,
+    pub target: Entity,
+    pub damage: i32,
+    /// Applied to the target along with the damage.
+    pub effects: Vec<StatusEffect>,
+    pub thrown: Option<ItemProperties>,
+}
+
+/// Adds the damage and on-hit effects of the attacker's equipped items,
+/// or of the thrown item if there is one.
 pub struct WeaponDamage;
 
 impl<'a> EventSystem<'a> for WeaponDamage {
-    type SystemData = ReadStorage<'a, components::Inventory>;
-    type Event = (Entity, i32);
-
-    fn run(Inventory: &Self::SystemData, event: &mut Self::Event) {
-        event.1 += Inventory.get(event.0).unwrap().items.len() as i32;
+    type SystemData = ReadStorage<'a, components::Equipment>;
+    type Event = DamageEvent;
+
+    fn run(equipment: &Self::SystemData, event: &mut Self::Event) {
+        if let Some(thrown) = &event.thrown {
+            event.damage += thrown.damage.unwrap_or(0);
+            event.effects.extend(thrown.on_hit.iter().cloned());
+        } else if let Some(equipment) = equipment.get(event.attacker) {
+            event.damage += equipment.damage();
+            event.effects.extend(equipment.on_hit());
+        }
     }
 }
 
-pub struct Test;
+/// Subtracts the defense of the target's equipped items. Hits always do at
+/// least 1 damage.
+pub struct ArmorDefense;
 
-impl<'a> EventSystem<'a> for Test {
-    type SystemData = ();
-    type Event = (Entity, i32);
+impl<'a> EventSystem<'a> for ArmorDefense {
+    type SystemData = ReadStorage<'a, components::Equipment>;
+    type Event = DamageEvent;
 
-    fn run(_: &Self::SystemData, event: &mut Self::Event) {
-        event.1 += 10;
+    fn run(equipment: &Self::SystemData, event: &mut Self::Event) {
+        let defense = equipment.get(event.target).map_or(0, |x| x.defense());
+        event.damage = (event.damage - defense).max(1);
     }
 }
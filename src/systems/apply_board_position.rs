@@ -1,9 +1,14 @@
-use crate::{components::WorldPosition, tui::Position};
+use crate::{
+    components::{PlayerControlledCharacter, WorldPosition},
+    resources::{Camera, WorldMap},
+    tui::{Position, Visible},
+};
 use amethyst::ecs::{prelude::*, SystemData as _};
 
 #[derive(Default)]
 pub struct ApplyBoardPositionSystem {
     worldpos_reader: Option<ReaderId<ComponentEvent>>,
+    old_camera: Camera,
 }
 
 #[derive(SystemData)]
@@ -11,6 +16,10 @@ pub struct SystemData<'s> {
     worldpos: WriteStorage<'s, WorldPosition>,
     pos: WriteStorage<'s, Position>,
     entities: Entities<'s>,
+    visible: WriteStorage<'s, Visible>,
+    player: ReadStorage<'s, PlayerControlledCharacter>,
+    map: Read<'s, WorldMap>,
+    camera: Read<'s, Camera>,
 }
 
 impl<'s> System<'s> for ApplyBoardPositionSystem {
@@ -33,13 +42,32 @@ impl<'s> System<'s> for ApplyBoardPositionSystem {
             }
         }
 
+        // Everything moves on the board when the camera does.
+        if *data.camera != self.old_camera {
+            self.old_camera = *data.camera;
+            for (entity, _) in (&data.entities, &data.worldpos).join() {
+                dirty.add(entity.id());
+            }
+        }
+
         for (entity, wp, _) in (&data.entities, data.worldpos.maybe(), &dirty).join() {
             if let Some(wp) = wp {
-                data.pos.insert(entity, Position::new(wp.x, wp.y)).ok();
+                let (x, y) = (wp.x - data.camera.x, wp.y - data.camera.y);
+                data.pos.insert(entity, Position::new(x, y)).ok();
             } else {
                 data.pos.remove(entity);
             }
         }
+
+        // Hide everything on the board the player can't currently see, or
+        // that is outside the camera's view.
+        for (entity, wp, _, ()) in (&data.entities, &data.worldpos, &data.pos, !&data.player).join()
+        {
+            let in_view = data.map.is_visible(wp) && data.camera.to_board(wp).is_some();
+            if data.visible.get(entity).map_or(true, |x| x.0) != in_view {
+                data.visible.insert(entity, Visible(in_view)).ok();
+            }
+        }
     }
 
     fn setup(&mut self, res: &mut Resources) {
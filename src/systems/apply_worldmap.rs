@@ -1,6 +1,6 @@
 use crate::{
     components::{Character, Item, WorldPosition},
-    resources::{WorldItem, WorldMap},
+    resources::{TileChanged, TileChanges, WorldItem, WorldMap},
 };
 use amethyst::ecs::{prelude::*, SystemData as _};
 
@@ -23,6 +23,7 @@ pub struct SystemData<'s> {
     entities: Entities<'s>,
     character: ReadStorage<'s, Character>,
     item: ReadStorage<'s, Item>,
+    tile_changes: Write<'s, TileChanges>,
 }
 
 impl<'s> System<'s> for ApplyWorldMapSystem {
@@ -49,14 +50,23 @@ impl<'s> System<'s> for ApplyWorldMapSystem {
 
         for (entity, wp, _) in (&data.entities, data.worldpos.maybe(), &dirty).join() {
             if let Some(OldWorldPosition(wp)) = data.old_worldpos.get(entity) {
+                data.tile_changes.single_write(TileChanged {
+                    pos: *wp,
+                    cause: Some(entity),
+                });
                 let tile = map.get_mut(wp).expect("Entity has illegal WorldPosition");
-                if data.character.contains(entity) {
+                // Somebody else may have stepped in already.
+                if data.character.contains(entity) && tile.character == Some(entity) {
                     tile.character = None;
                 } else if data.item.contains(entity) {
                     tile.items.retain(|x| x.entity != entity);
                 }
             }
             if let Some(wp) = wp {
+                data.tile_changes.single_write(TileChanged {
+                    pos: *wp,
+                    cause: Some(entity),
+                });
                 let tile = map.get_mut(wp).expect("Entity has illegal WorldPosition");
                 if data.character.contains(entity) {
                     tile.character = Some(entity);
@@ -0,0 +1,74 @@
+use crate::{
+    components::BoardDisplay,
+    data::{calculate_hash, Terrain, TileVisibility},
+    resources::{Camera, WorldMap, WorldTile},
+    tui::{Color, Position, Style, TextBlock},
+};
+use amethyst::ecs::prelude::*;
+
+#[derive(Default)]
+pub struct BoardDisplaySystem {
+    old_hash: Option<u64>,
+}
+
+#[derive(SystemData)]
+pub struct SystemData<'s> {
+    text_block: WriteStorage<'s, TextBlock>,
+    board_display: ReadStorage<'s, BoardDisplay>,
+    world_map: Read<'s, WorldMap>,
+    camera: Read<'s, Camera>,
+}
+
+impl<'s> System<'s> for BoardDisplaySystem {
+    type SystemData = SystemData<'s>;
+
+    fn run(&mut self, mut data: Self::SystemData) {
+        let (camera, map) = (&*data.camera, &*data.world_map);
+        let cells: Vec<Vec<(char, Style)>> = (0..camera.height)
+            .map(|y| {
+                (0..camera.width)
+                    .map(|x| {
+                        map.get(&camera.to_world(Position::new(x, y)))
+                            .map_or((' ', Style::default()), tile_cell)
+                    })
+                    .collect()
+            })
+            .collect();
+
+        let hash = Some(calculate_hash(&cells));
+        if hash == self.old_hash {
+            return;
+        }
+        self.old_hash = hash;
+
+        // The size of the block is left to the `CameraSystem`.
+        for (block, _) in (&mut data.text_block, &data.board_display).join() {
+            block.rows = cells
+                .iter()
+                .map(|row| row.iter().map(|cell| cell.0).collect())
+                .collect();
+            block.styles = cells
+                .iter()
+                .map(|row| row.iter().map(|cell| cell.1).collect())
+                .collect();
+        }
+    }
+}
+
+/// Remembered tiles are dimmed, tiles that were never seen stay blank.
+fn tile_cell(tile: &WorldTile) -> (char, Style) {
+    match (tile.visibility, tile.terrain) {
+        (TileVisibility::Visible, terrain) => (terrain.glyph(), terrain_style(terrain)),
+        (TileVisibility::Remembered, terrain) => (terrain.glyph(), terrain_style(terrain).dim()),
+        (TileVisibility::Unseen, _) => (' ', Style::default()),
+    }
+}
+
+fn terrain_style(terrain: Terrain) -> Style {
+    match terrain {
+        Terrain::Floor | Terrain::Wall | Terrain::Rubble => Style::new(),
+        Terrain::ClosedDoor | Terrain::OpenDoor => Style::new().fg(Color::Yellow),
+        Terrain::Water => Style::new().fg(Color::Blue),
+        Terrain::StairsDown | Terrain::StairsUp => Style::new().fg(Color::White).bold(),
+    }
+}
@@ -0,0 +1,58 @@
+use crate::{
+    components::{BoardDisplay, PlayerControlledCharacter, WorldPosition},
+    resources::{Camera, WorldMap},
+    tui::{Parent, TextBlock},
+};
+use amethyst::ecs::prelude::*;
+
+/// Sizes the board to the panel the `StackingSystem` gave it and keeps the
+/// `Camera` on the player.
+pub struct CameraSystem;
+
+#[derive(SystemData)]
+pub struct SystemData<'s> {
+    camera: Write<'s, Camera>,
+    text_block: WriteStorage<'s, TextBlock>,
+    board_display: ReadStorage<'s, BoardDisplay>,
+    parent: ReadStorage<'s, Parent>,
+    player: ReadStorage<'s, PlayerControlledCharacter>,
+    worldpos: ReadStorage<'s, WorldPosition>,
+    map: Read<'s, WorldMap>,
+    entities: Entities<'s>,
+}
+
+impl<'s> System<'s> for CameraSystem {
+    type SystemData = SystemData<'s>;
+
+    fn run(&mut self, mut data: Self::SystemData) {
+        let mut camera = *data.camera;
+
+        for (entity, _, parent) in (&data.entities, &data.board_display, &data.parent).join() {
+            let (width, height) = match data.text_block.get(parent.entity) {
+                Some(panel) => (panel.width.max(0), panel.height.max(0)),
+                None => continue,
+            };
+            camera.width = width;
+            camera.height = height;
+
+            let resized = data
+                .text_block
+                .get(entity)
+                .map_or(true, |block| block.width != width || block.height != height);
+            if resized {
+                if let Some(block) = data.text_block.get_mut(entity) {
+                    block.width = width;
+                    block.height = height;
+                }
+            }
+        }
+
+        if let Some((wp, _)) = (&data.worldpos, &data.player).join().next() {
+            camera.follow(wp, data.map.width as i32, data.map.height as i32);
+        }
+
+        if camera != *data.camera {
+            *data.camera = camera;
+        }
+    }
+}
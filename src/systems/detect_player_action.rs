@@ -1,11 +1,11 @@
 use crate::{
     components::{
-        Controlled, Inventory, InventoryDisplay, InventoryDisplayKind, PlayerControlledCharacter,
-        WorldPosition,
+        Controlled, Equipment, Inventory, InventoryDisplay, InventoryDisplayKind, LogDisplay,
+        PlayerControlledCharacter, WorldPosition,
     },
-    data::{Direction, PlayerAction},
+    data::{Direction, PlayerAction, Stairs},
     resources::{LogEvents, PlayerActionResource, WorldMap},
-    tui::Key,
+    tui::{Key, Viewport},
 };
 use amethyst::{
     core::{shrev::EventChannel, timing::Time},
@@ -15,6 +15,10 @@ use amethyst::{
 #[derive(Default)]
 pub struct DetectPlayerActionSystem {
     reader: Option<ReaderId<Key>>,
+    /// Carried item waiting for a direction to be thrown in.
+    throwing: Option<usize>,
+    /// Waiting for a direction to attack in.
+    attacking: bool,
 }
 
 #[derive(SystemData)]
@@ -25,10 +29,13 @@ pub struct SystemData<'s> {
     log: Read<'s, LogEvents>,
     player: ReadStorage<'s, PlayerControlledCharacter>,
     inventory: WriteStorage<'s, Inventory>,
+    equipment: WriteStorage<'s, Equipment>,
     inventory_display: WriteStorage<'s, InventoryDisplay>,
     position: ReadStorage<'s, WorldPosition>,
     controlled: WriteStorage<'s, Controlled>,
-    world_map: Write<'s, WorldMap>,
+    viewport: WriteStorage<'s, Viewport>,
+    log_display: ReadStorage<'s, LogDisplay>,
+    world_map: Read<'s, WorldMap>,
     entities: Entities<'s>,
 }
 
@@ -40,6 +47,15 @@ impl<'s> System<'s> for DetectPlayerActionSystem {
 
         let mut action = None;
         let mut remove_control = None;
+        let mut focus = None;
+
+        // Once everything on the ground has been picked up there is nothing
+        // left to control.
+        let ground_empty = (&data.position, &data.player).join().all(|(position, _)| {
+            data.world_map
+                .get(position)
+                .map_or(true, |x| x.items.is_empty())
+        });
 
         for (entity, display, _active) in (
             &data.entities,
@@ -48,6 +64,11 @@ impl<'s> System<'s> for DetectPlayerActionSystem {
         )
             .join()
         {
+            if display.display_kind == InventoryDisplayKind::Ground && ground_empty {
+                remove_control = Some(entity);
+                display.cursor_pos = None;
+                continue;
+            }
             for key in data.inputs.read(self.reader.as_mut().unwrap()) {
                 match key {
                     Key::Character('\u{1b}') => action = Some(PlayerAction::Quit),
@@ -55,35 +76,81 @@ impl<'s> System<'s> for DetectPlayerActionSystem {
                         remove_control = Some(entity);
                         display.cursor_pos = None;
                     }
-                    Key::Character('w') => {
+                    Key::Character('w') | Key::Character('k') | Key::Character('8') => {
                         display.cursor_pos = Some(display.cursor_pos.map_or(0, |x| 0.max(x - 1)));
                     }
-                    Key::Character('s') => {
+                    Key::Character('s') | Key::Character('j') | Key::Character('2') => {
                         display.cursor_pos = Some(display.cursor_pos.map_or(0, |x| 0.max(x + 1)));
                     }
+                    Key::KeyPPage | Key::KeyNPage => {
+                        let page = data.viewport.get(entity).map_or(1, |x| x.page.max(1)) as i32;
+                        let step = if *key == Key::KeyPPage { -page } else { page };
+                        display.cursor_pos = Some(0.max(display.cursor_pos.unwrap_or(0) + step));
+                    }
                     Key::Character(' ') => {
                         if display.display_kind == InventoryDisplayKind::Ground {
-                            for (position, inventory, _player) in
-                                (&data.position, &mut data.inventory, &data.player).join()
+                            let index = display.cursor_pos.unwrap_or(0) as usize;
+                            action = Some(PlayerAction::PickUp(index));
+                        }
+                    }
+                    Key::Character('e') => {
+                        if display.display_kind == InventoryDisplayKind::Own {
+                            for (inventory, equipment, _player) in
+                                (&mut data.inventory, &mut data.equipment, &data.player).join()
                             {
-                                if let Some(tile) = data.world_map.get_mut(position) {
-                                    if tile.items.len() > display.cursor_pos.unwrap_or(0) as usize {
-                                        let item = tile
-                                            .items
-                                            .remove(display.cursor_pos.unwrap_or(0) as usize);
-                                        data.entities.delete(item.entity).ok();
-                                        data.log
-                                            .send(format!("Grabbed {}", item.item.description()));
-                                        inventory.items.push(item.item);
-                                        if tile.items.len() == 0 {
-                                            remove_control = Some(entity);
-                                            display.cursor_pos = None;
-                                        }
+                                let selection = selected(display, inventory, equipment);
+                                if let Some(message) =
+                                    toggle_equipped(inventory, equipment, selection)
+                                {
+                                    data.log.send(message);
+                                }
+                            }
+                        }
+                    }
+                    Key::Character('u') | Key::Character('d') | Key::Character('t') => {
+                        if display.display_kind == InventoryDisplayKind::Own {
+                            for (inventory, equipment, _player) in
+                                (&data.inventory, &data.equipment, &data.player).join()
+                            {
+                                let index = match selected(display, inventory, equipment) {
+                                    Selected::Carried(index) => index,
+                                    Selected::Worn(_) => {
+                                        data.log.send("Take it off first");
+                                        continue;
+                                    }
+                                    Selected::Nothing => continue,
+                                };
+                                let item = &inventory.items[index];
+                                match key {
+                                    Key::Character('u') if item.properties().is_usable() => {
+                                        action = Some(PlayerAction::Use(index));
+                                    }
+                                    Key::Character('u') => data
+                                        .log
+                                        .send(format!("{} can't be used", item.description())),
+                                    Key::Character('d') => {
+                                        action = Some(PlayerAction::Drop(index));
+                                    }
+                                    _ => {
+                                        self.throwing = Some(index);
+                                        remove_control = Some(entity);
+                                        display.cursor_pos = None;
+                                        data.log.send("Throw in which direction?");
                                     }
                                 }
                             }
                         }
                     }
+                    Key::Character('o') => {
+                        if display.display_kind == InventoryDisplayKind::Own {
+                            display.sort = display.sort.next();
+                        }
+                    }
+                    Key::Character('f') => {
+                        if display.display_kind == InventoryDisplayKind::Own {
+                            display.next_filter();
+                        }
+                    }
                     x => {
                         data.log.send(format!("Unrecognized input: {:?}", x));
                     }
@@ -98,26 +165,104 @@ impl<'s> System<'s> for DetectPlayerActionSystem {
         for key in data.inputs.read(self.reader.as_mut().unwrap()) {
             match key {
                 Key::Character('\u{1b}') => action = Some(PlayerAction::Quit),
-                Key::Character('w') => action = Some(PlayerAction::Move(Up)),
-                Key::Character('s') => action = Some(PlayerAction::Move(Down)),
-                Key::Character('a') => action = Some(PlayerAction::Move(Left)),
-                Key::Character('d') => action = Some(PlayerAction::Move(Right)),
-                Key::Character('x') => action = Some(PlayerAction::Wait),
+                Key::Character('w') | Key::Character('k') | Key::Character('8') => {
+                    action = Some(PlayerAction::Move(Up))
+                }
+                Key::Character('s') | Key::Character('j') | Key::Character('2') => {
+                    action = Some(PlayerAction::Move(Down))
+                }
+                Key::Character('a') | Key::Character('h') | Key::Character('4') => {
+                    action = Some(PlayerAction::Move(Left))
+                }
+                Key::Character('d') | Key::Character('l') | Key::Character('6') => {
+                    action = Some(PlayerAction::Move(Right))
+                }
+                Key::Character('y') | Key::Character('7') => {
+                    action = Some(PlayerAction::Move(UpLeft))
+                }
+                Key::Character('u') | Key::Character('9') => {
+                    action = Some(PlayerAction::Move(UpRight))
+                }
+                Key::Character('b') | Key::Character('1') => {
+                    action = Some(PlayerAction::Move(DownLeft))
+                }
+                Key::Character('n') | Key::Character('3') => {
+                    action = Some(PlayerAction::Move(DownRight))
+                }
+                Key::Character('x') | Key::Character('5') => action = Some(PlayerAction::Wait),
                 Key::Character('g') => action = Some(PlayerAction::Grab),
+                Key::Character('f') => {
+                    self.attacking = true;
+                    data.log.send("Attack in which direction?");
+                }
+                Key::Character('>') => action = Some(PlayerAction::TakeStairs(Stairs::Down)),
+                Key::Character('<') => action = Some(PlayerAction::TakeStairs(Stairs::Up)),
+                Key::Character('i') => focus = Some(InventoryDisplayKind::Own),
+                Key::KeyPPage | Key::KeyNPage => {
+                    for (viewport, _) in (&mut data.viewport, &data.log_display).join() {
+                        if *key == Key::KeyPPage {
+                            viewport.page_up();
+                        } else {
+                            viewport.page_down();
+                        }
+                    }
+                }
                 x => {
                     data.log.send(format!("Unrecognized input: {:?}", x));
                 }
             }
         }
 
+        if let Some(index) = self.throwing {
+            match action {
+                Some(PlayerAction::Move(dir)) => {
+                    action = Some(PlayerAction::Throw(index, dir));
+                    self.throwing = None;
+                }
+                Some(PlayerAction::Quit) | None => {}
+                Some(_) => {
+                    data.log.send("Never mind");
+                    action = None;
+                    self.throwing = None;
+                }
+            }
+        }
+
+        if self.attacking {
+            match action {
+                Some(PlayerAction::Move(dir)) => {
+                    self.attacking = false;
+                    action = None;
+                    let target = (&data.position, &data.player)
+                        .join()
+                        .find_map(|(position, _)| data.world_map.read(&position.step_dir(dir)));
+                    if target.is_some() {
+                        action = Some(PlayerAction::Attack(dir));
+                    } else {
+                        data.log.send("There is nobody to attack");
+                    }
+                }
+                Some(PlayerAction::Quit) | None => {}
+                Some(_) => {
+                    data.log.send("Never mind");
+                    action = None;
+                    self.attacking = false;
+                }
+            }
+        }
+
         if action == Some(PlayerAction::Grab) {
+            focus = Some(InventoryDisplayKind::Ground);
+            action = None;
+        }
+
+        if let Some(kind) = focus {
             for (entity, inventory) in (&data.entities, &mut data.inventory_display).join() {
-                if inventory.display_kind == InventoryDisplayKind::Ground {
+                if inventory.display_kind == kind {
                     inventory.cursor_pos = Some(0);
                     data.controlled.insert(entity, Controlled).ok();
                 }
             }
-            action = None;
         }
 
         data.action.hold_delay -= data.time.delta_seconds();
@@ -132,7 +277,6 @@ impl<'s> System<'s> for DetectPlayerActionSystem {
 
     fn setup(&mut self, res: &mut Resources) {
         Self::SystemData::setup(res);
-        res.insert(EventChannel::<Key>::new());
         self.reader = Some(
             res.get_mut::<EventChannel<Key>>()
                 .unwrap()
@@ -140,3 +284,62 @@ impl<'s> System<'s> for DetectPlayerActionSystem {
         );
     }
 }
+
+/// The row under the cursor of the own inventory display.
+enum Selected {
+    Worn(usize),
+    /// Index into `Inventory::items`.
+    Carried(usize),
+    Nothing,
+}
+
+/// Worn items come first, followed by the carried items that pass the
+/// display's filter, in its sort order.
+fn selected(display: &InventoryDisplay, inventory: &Inventory, equipment: &Equipment) -> Selected {
+    let cursor = display.cursor_pos.unwrap_or(0) as usize;
+    if cursor < equipment.items.len() {
+        return Selected::Worn(cursor);
+    }
+    match display
+        .carried_rows(&inventory.items)
+        .get(cursor - equipment.items.len())
+    {
+        Some(&index) => Selected::Carried(index),
+        None => Selected::Nothing,
+    }
+}
+
+/// Takes off the selected worn item, or puts on the selected carried one.
+/// Returns what to tell the player.
+fn toggle_equipped(
+    inventory: &mut Inventory,
+    equipment: &mut Equipment,
+    selection: Selected,
+) -> Option<String> {
+    let index = match selection {
+        Selected::Worn(index) => {
+            let item = equipment.unequip(index)?;
+            let message = format!("You take off {}", item.description());
+            inventory.add(item);
+            return Some(message);
+        }
+        Selected::Carried(index) => index,
+        Selected::Nothing => return None,
+    };
+
+    match inventory.items[index].properties().slot {
+        Some(slot) => {
+            let item = inventory.take(index);
+            let mut message = format!("You equip {}", item.description());
+            if let Some(removed) = equipment.equip(slot, item) {
+                message.push_str(&format!(" instead of {}", removed.description()));
+                inventory.add(removed);
+            }
+            Some(message)
+        }
+        None => Some(format!(
+            "{} can't be equipped",
+            inventory.items[index].description()
+        )),
+    }
+}
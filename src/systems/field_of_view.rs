@@ -0,0 +1,27 @@
+use crate::{
+    components::{PlayerControlledCharacter, WorldPosition},
+    fov::{self, PLAYER_SIGHT_RADIUS},
+    resources::WorldMap,
+};
+use amethyst::ecs::prelude::*;
+
+pub struct FieldOfViewSystem;
+
+#[derive(SystemData)]
+pub struct SystemData<'s> {
+    worldpos: ReadStorage<'s, WorldPosition>,
+    player: ReadStorage<'s, PlayerControlledCharacter>,
+    map: Write<'s, WorldMap>,
+}
+
+impl<'s> System<'s> for FieldOfViewSystem {
+    type SystemData = SystemData<'s>;
+
+    fn run(&mut self, mut data: Self::SystemData) {
+        let origin = match (&data.worldpos, &data.player).join().next() {
+            Some((wp, _)) => *wp,
+            None => return,
+        };
+        fov::update_visibility(&mut data.map, &origin, PLAYER_SIGHT_RADIUS);
+    }
+}
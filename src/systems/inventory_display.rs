@@ -1,29 +1,30 @@
 use crate::{
     components::{
-        Inventory, InventoryDisplay, InventoryDisplayKind, Item, PlayerControlledCharacter,
+        Equipment, Inventory, InventoryDisplay, InventoryDisplayKind, PlayerControlledCharacter,
         WorldPosition,
     },
     data::calculate_hash,
     resources::WorldMap,
     specs_ext::SpecsExt,
-    tui::{TextBlock, TuiChannel, TuiEvent, Visible},
+    tui::{text::fit_width, Style, TuiChannel, TuiEvent, Viewport, Visible},
 };
 use amethyst::ecs::{prelude::*, SystemData as _};
-use std::borrow::Borrow;
 
 #[derive(Default)]
 pub struct InventoryDisplaySystem {
     old_inventory_hash: Option<u64>,
     old_ground_hash: Option<u64>,
+    old_viewport_hash: Option<u64>,
     tui_reader: Option<ReaderId<TuiEvent>>,
     display_reader: Option<ReaderId<ComponentEvent>>,
 }
 
 #[derive(SystemData)]
 pub struct SystemData<'s> {
-    text_block: WriteStorage<'s, TextBlock>,
+    viewport: WriteStorage<'s, Viewport>,
     inventory_display: ReadStorage<'s, InventoryDisplay>,
     inventory: ReadStorage<'s, Inventory>,
+    equipment: ReadStorage<'s, Equipment>,
     player: ReadStorage<'s, PlayerControlledCharacter>,
     position: ReadStorage<'s, WorldPosition>,
     world_map: Read<'s, WorldMap>,
@@ -57,6 +58,17 @@ impl<'s> System<'s> for InventoryDisplaySystem {
             }
         }
 
+        // Rows are fitted to the viewports, which follow their text blocks.
+        let sizes: Vec<(usize, usize)> = (&data.viewport, &data.inventory_display)
+            .join()
+            .map(|(viewport, _)| (viewport.width, viewport.page))
+            .collect();
+        let viewport_hash = Some(calculate_hash(&sizes));
+        if viewport_hash != self.old_viewport_hash {
+            self.old_viewport_hash = viewport_hash;
+            dirty_displays = true;
+        }
+
         for event in data.tui_channel.read(self.tui_reader.as_mut().unwrap()) {
             match event {
                 TuiEvent::TextBlock {
@@ -72,15 +84,19 @@ impl<'s> System<'s> for InventoryDisplaySystem {
             }
         }
 
-        for (inventory, _player) in (&data.inventory, &data.player).join() {
-            let hash = Some(calculate_hash(&inventory.items));
+        for (entity, inventory, _player) in (&data.entities, &data.inventory, &data.player).join() {
+            let display = (&data.inventory_display)
+                .join()
+                .find(|x| x.display_kind == InventoryDisplayKind::Own);
+            let lines = own_lines(inventory, data.equipment.get(entity), display);
+            let hash = Some(calculate_hash(&lines));
             let dirty = hash != self.old_inventory_hash || dirty_displays;
             self.old_inventory_hash = hash;
             if dirty {
                 build_inventory(
-                    &inventory.items,
+                    &lines,
                     &data.entities,
-                    &mut data.text_block,
+                    &mut data.viewport,
                     &data.inventory_display,
                     InventoryDisplayKind::Own,
                     &mut data.visible,
@@ -90,19 +106,23 @@ impl<'s> System<'s> for InventoryDisplaySystem {
         }
         for (position, _player) in (&data.position, &data.player).join() {
             if let Some(tile) = data.world_map.get(position) {
-                let items = tile.items.iter().map(|x| &x.item).collect::<Vec<_>>();
-                let hash = Some(calculate_hash(&items));
+                let lines = tile
+                    .items
+                    .iter()
+                    .map(|x| x.item.description())
+                    .collect::<Vec<_>>();
+                let hash = Some(calculate_hash(&lines));
                 let dirty = hash != self.old_ground_hash || dirty_displays;
                 self.old_ground_hash = hash;
                 if dirty {
                     build_inventory(
-                        &items,
+                        &lines,
                         &data.entities,
-                        &mut data.text_block,
+                        &mut data.viewport,
                         &data.inventory_display,
                         InventoryDisplayKind::Ground,
                         &mut data.visible,
-                        items.len() > 0,
+                        !lines.is_empty(),
                     );
                 }
             }
@@ -117,52 +137,81 @@ impl<'s> System<'s> for InventoryDisplaySystem {
     }
 }
 
-fn build_inventory<T>(
-    items: &[T],
+/// Worn items come first, so cursor positions below `equipment.items.len()`
+/// refer to equipment and the rest to the sorted and filtered inventory.
+fn own_lines(
+    inventory: &Inventory,
+    equipment: Option<&Equipment>,
+    display: Option<&InventoryDisplay>,
+) -> Vec<String> {
+    let worn = equipment.into_iter().flat_map(|x| x.items.iter());
+    let carried = match display {
+        Some(display) => display.carried_rows(&inventory.items),
+        None => (0..inventory.items.len()).collect(),
+    };
+    worn.map(|(slot, item)| format!("[{}] {}", slot.name(), item.description()))
+        .chain(
+            carried
+                .into_iter()
+                .map(|i| inventory.items[i].description()),
+        )
+        .collect()
+}
+
+fn build_inventory(
+    lines: &[String],
     entities: &Entities,
-    text_block: &mut WriteStorage<TextBlock>,
+    viewport: &mut WriteStorage<Viewport>,
     inventory_display: &ReadStorage<InventoryDisplay>,
     kind: InventoryDisplayKind,
     visible: &mut WriteStorage<Visible>,
     is_visible: bool,
-) where
-    T: Borrow<Item>,
-{
+) {
     let title = match kind {
         InventoryDisplayKind::Own => "Inventory",
         InventoryDisplayKind::Ground => "Items on the ground",
     };
-    for (entity, block, display) in (entities, text_block, inventory_display).join() {
+    for (entity, viewport, display) in (entities, viewport, inventory_display).join() {
         if display.display_kind != kind {
             continue;
         }
         visible.get_mut_or_default(entity).0 = is_visible || display.cursor_pos.is_some();
-        block.rows = [title, ""]
+        let subtitle = match kind {
+            InventoryDisplayKind::Own => display.modes_description(),
+            InventoryDisplayKind::Ground => String::new(),
+        };
+        let cursor = display.cursor_pos.map(|x| x.max(0) as usize);
+        // Empty rows fill the panel and reach down to the cursor.
+        let row_count = lines
+            .len()
+            .max(viewport.page)
+            .max(cursor.map_or(0, |x| x + 1));
+        let width = viewport.width;
+        viewport.follow = cursor;
+        viewport.rows = [title.to_owned(), subtitle]
             .into_iter()
-            .map(|x| (*x).to_owned())
+            .cloned()
             .chain(
-                items
+                lines
                     .iter()
-                    .map(|item| item.borrow().description())
+                    .cloned()
                     .chain(["".to_owned()].into_iter().cycle().cloned())
                     .enumerate()
                     .map(|(i, x)| {
-                        format!(
-                            "*{} {}",
-                            if Some(i as i32) == display.cursor_pos {
-                                " >"
-                            } else {
-                                ""
-                            },
-                            x
+                        fit_width(
+                            &format!("*{} {}", if Some(i) == cursor { " >" } else { "" }, x),
+                            width,
                         )
-                        .chars()
-                        .chain([' '].into_iter().cycle().cloned())
-                        .take(block.width as usize - 1)
-                        .collect()
-                    }),
+                    })
+                    .take(row_count),
             )
-            .take(block.height as usize + 50)
             .collect();
+        viewport.styles = match cursor {
+            Some(cursor) => (0..cursor + viewport.header)
+                .map(|_| Vec::new())
+                .chain(Some(vec![Style::new().reverse(); width]))
+                .collect(),
+            None => Vec::new(),
+        };
     }
 }
@@ -1,50 +1,68 @@
 use crate::{
     components::LogDisplay,
-    resources::{EventLog, LogEvents, TurnCounter},
-    tui::TextBlock,
+    resources::{Dungeon, EventLog, GameRng, LogEvents, LogLine, Scheduler},
+    tui::{Style, Viewport},
 };
 use amethyst::{core::Time, ecs::prelude::*};
 
 #[derive(Default)]
-pub struct LogDisplaySystem;
+pub struct LogDisplaySystem {
+    /// Row styles are as wide as the viewport, so they are redone when it
+    /// changes width.
+    width: usize,
+}
 
 #[derive(SystemData)]
 pub struct SystemData<'s> {
     log_events: Write<'s, LogEvents>,
     log: Write<'s, EventLog>,
-    text_block: WriteStorage<'s, TextBlock>,
+    viewport: WriteStorage<'s, Viewport>,
     log_display: ReadStorage<'s, LogDisplay>,
     time: Read<'s, Time>,
-    turn: Read<'s, TurnCounter>,
+    scheduler: Read<'s, Scheduler>,
+    rng: Read<'s, GameRng>,
+    dungeon: Read<'s, Dungeon>,
 }
 
 impl<'s> System<'s> for LogDisplaySystem {
     type SystemData = SystemData<'s>;
 
     fn run(&mut self, mut data: Self::SystemData) {
-        let mut dirty = false;
+        let mut new_lines = 0;
         data.log.events.reverse();
         while let Ok(line) = data.log_events.receiver().try_recv() {
-            data.log.events.push(line.0);
-            dirty = true;
+            data.log.events.push(line);
+            new_lines += 1;
         }
         data.log.events.reverse();
 
-        if dirty {
-            for (block, _) in (&mut data.text_block, &data.log_display).join() {
-                block.rows = ["".to_owned()]
-                    .iter()
+        for (viewport, _) in (&mut data.viewport, &data.log_display).join() {
+            // Newest lines are on top. Keep showing the same lines when
+            // scrolled back.
+            if viewport.offset > 0 {
+                viewport.scroll_by(new_lines);
+            }
+            if new_lines > 0 || viewport.width != self.width {
+                self.width = viewport.width;
+                let header = LogLine::styled("", Style::new().bold());
+                let lines: Vec<&LogLine> = Some(&header)
+                    .into_iter()
                     .chain(data.log.events.iter())
-                    .chain(["".to_owned()].into_iter().cycle())
-                    .cloned()
-                    .take(block.height as usize + 50)
+                    .collect();
+                viewport.rows = lines.iter().map(|line| line.text.clone()).collect();
+                viewport.styles = lines
+                    .iter()
+                    .map(|line| vec![line.style; viewport.width])
                     .collect();
             }
-        }
-        for (block, _) in (&mut data.text_block, &data.log_display).join() {
-            if block.rows.len() > 0 {
-                block.rows[0] =
-                    format!("Turn {}, delta {}", data.turn.0, data.time.delta_seconds());
+            if viewport.rows.len() > 0 {
+                viewport.rows[0] = format!(
+                    "Depth {}, seed {}, turn {}, delta {}",
+                    data.dungeon.depth + 1,
+                    data.rng.seed(),
+                    data.scheduler.turn(),
+                    data.time.delta_seconds()
+                );
             }
         }
     }
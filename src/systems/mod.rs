@@ -1,18 +1,31 @@
-mod aggressive_ai;
-mod apply_attacks;
 mod apply_board_position;
-mod apply_movement;
 mod apply_worldmap;
+mod board_display;
+mod camera;
 mod detect_player_action;
+mod field_of_view;
 mod inventory_display;
 mod log_display;
-mod player_movement;
+mod monster_ai;
+mod player_control;
+mod record_input;
+mod resolve_actions;
+mod scheduler;
 mod stun;
 
 pub use self::{
-    aggressive_ai::AggressiveAISystem, apply_attacks::ApplyAttacksSystem,
-    apply_board_position::ApplyBoardPositionSystem, apply_movement::ApplyMovementSystem,
-    apply_worldmap::ApplyWorldMapSystem, detect_player_action::DetectPlayerActionSystem,
-    inventory_display::InventoryDisplaySystem, log_display::LogDisplaySystem,
-    player_movement::PlayerMovementSystem, stun::StunSystem,
+    apply_board_position::ApplyBoardPositionSystem,
+    apply_worldmap::{ApplyWorldMapSystem, OldWorldPosition},
+    board_display::BoardDisplaySystem,
+    camera::CameraSystem,
+    detect_player_action::DetectPlayerActionSystem,
+    field_of_view::FieldOfViewSystem,
+    inventory_display::InventoryDisplaySystem,
+    log_display::LogDisplaySystem,
+    monster_ai::MonsterAISystem,
+    player_control::PlayerControlSystem,
+    record_input::RecordInputSystem,
+    resolve_actions::ResolveActionsSystem,
+    scheduler::SchedulerSystem,
+    stun::StunSystem,
 };
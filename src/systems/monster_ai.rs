@@ -0,0 +1,317 @@
+use crate::{
+    ai::{self, Intent, Situation, Target},
+    components::{
+        AggressionTarget, Ai, CachedPath, Dead, Health, LastAction, PlayerControlledCharacter,
+        Team, WorldPosition, SEARCH_TURNS,
+    },
+    data::{Action, Behaviour, Direction},
+    fov::{self, MONSTER_SIGHT_RADIUS},
+    pathfinding,
+    resources::{
+        ActionQueue, ActionRequest, Factions, GameRng, Noise, Noises, Scheduler, TileChanged,
+        TileChanges, WorldMap,
+    },
+};
+use amethyst::ecs::{prelude::*, SystemData as _};
+
+/// Keeps track of what every monster sees and hears, and lets the
+/// behaviours of the monsters whose turn it is decide what to do.
+#[derive(Default)]
+pub struct MonsterAISystem {
+    tile_reader: Option<ReaderId<TileChanged>>,
+    noise_reader: Option<ReaderId<Noise>>,
+}
+
+#[derive(SystemData)]
+pub struct SystemData<'s> {
+    worldpos: ReadStorage<'s, WorldPosition>,
+    ai: ReadStorage<'s, Ai>,
+    teams: ReadStorage<'s, Team>,
+    factions: Read<'s, Factions>,
+    health: ReadStorage<'s, Health>,
+    actions: Read<'s, ActionQueue>,
+    last_action: ReadStorage<'s, LastAction>,
+    entities: Entities<'s>,
+    player: ReadStorage<'s, PlayerControlledCharacter>,
+    target: WriteStorage<'s, AggressionTarget>,
+    dead: ReadStorage<'s, Dead>,
+    map: Read<'s, WorldMap>,
+    path: WriteStorage<'s, CachedPath>,
+    tile_changes: Read<'s, TileChanges>,
+    noises: Read<'s, Noises>,
+    scheduler: Read<'s, Scheduler>,
+    rng: Write<'s, GameRng>,
+}
+
+impl<'s> System<'s> for MonsterAISystem {
+    type SystemData = SystemData<'s>;
+
+    fn run(&mut self, mut data: Self::SystemData) {
+        let changes = data
+            .tile_changes
+            .read(self.tile_reader.as_mut().unwrap())
+            .cloned()
+            .collect::<Vec<_>>();
+
+        let noises = data
+            .noises
+            .read(self.noise_reader.as_mut().unwrap())
+            .cloned()
+            .collect::<Vec<_>>();
+
+        // Everybody hears, whether it's their turn or not.
+        for (entity, wp, _, (), ()) in (
+            &data.entities,
+            &data.worldpos,
+            &data.ai,
+            !&data.player,
+            !&data.dead,
+        )
+            .join()
+        {
+            let tracked = data.target.get(entity).map(|x| x.target);
+            let heard = noises
+                .iter()
+                .filter(|noise| noise.pos.distance(wp) <= noise.radius)
+                .filter(|noise| tracked.map_or(true, |target| target == noise.source))
+                .filter(|noise| is_hostile(&data, entity, noise.source))
+                .last()
+                .cloned();
+            if let Some(noise) = heard {
+                match data.target.get_mut(entity) {
+                    Some(target) => {
+                        target.last_known = noise.pos;
+                        target.search = SEARCH_TURNS;
+                    }
+                    None => {
+                        data.target
+                            .insert(entity, AggressionTarget::new(noise.source, noise.pos))
+                            .expect("Adding AggressionTarget failed");
+                    }
+                }
+            }
+        }
+
+        for (entity, wp, ai, (), ()) in (
+            &data.entities,
+            &data.worldpos,
+            &data.ai,
+            !&data.player,
+            !&data.dead,
+        )
+            .join()
+        {
+            if !data.scheduler.is_acting(entity) {
+                continue;
+            }
+            // Whatever blocked the last step isn't on the cached path.
+            if let Some(LastAction { result: Err(_), .. }) = data.last_action.get(entity) {
+                data.path.remove(entity);
+            }
+
+            // Forget targets that died.
+            if let Some(target) = data.target.get(entity).map(|x| x.target) {
+                if data.dead.contains(target) || !data.entities.is_alive(target) {
+                    data.target.remove(entity);
+                    data.path.remove(entity);
+                }
+            }
+            let team = data.teams.get(entity).map(|x| x.0);
+            let visible = visible_hostiles(&data, entity, wp);
+            let tracked = data.target.get(entity).map(|x| x.target);
+            if let Some(&(_, target_wp)) = visible.iter().find(|x| Some(x.0) == tracked) {
+                let target = data.target.get_mut(entity).unwrap();
+                target.last_known = target_wp;
+                target.search = SEARCH_TURNS;
+            } else if let Some(&(target, target_wp)) = visible
+                .iter()
+                .min_by_key(|(_, target_wp)| target_wp.distance(wp))
+            {
+                data.target
+                    .insert(entity, AggressionTarget::new(target, target_wp))
+                    .expect("Adding AggressionTarget failed");
+            } else if tracked.is_none() {
+                if let Some((target, last_known)) = pack_target(&data, entity, wp, ai, team) {
+                    data.target
+                        .insert(entity, AggressionTarget::new(target, last_known))
+                        .expect("Adding AggressionTarget failed");
+                }
+            } else {
+                // Out of sight: search for a while, then give up.
+                let target = data.target.get_mut(entity).unwrap();
+                if target.search == 0 {
+                    data.target.remove(entity);
+                    data.path.remove(entity);
+                } else {
+                    target.search -= 1;
+                }
+            }
+
+            let target = data.target.get(entity).map(|x| Target {
+                entity: x.target,
+                position: x.last_known,
+                in_sight: visible.iter().any(|(visible, _)| *visible == x.target),
+            });
+            let allies = (
+                &data.entities,
+                &data.worldpos,
+                &data.teams,
+                &data.ai,
+                !&data.dead,
+            )
+                .join()
+                .filter(|(other, _, other_team, _, ())| {
+                    *other != entity && Some(other_team.0) == team
+                })
+                .map(|(_, ally_wp, _, _, ())| *ally_wp)
+                .collect();
+            let health = data
+                .health
+                .get(entity)
+                .map_or(100, |x| x.health * 100 / x.max.max(1));
+            let situation = Situation {
+                map: &data.map,
+                position: *wp,
+                health,
+                target,
+                allies,
+                home: ai.home,
+            };
+
+            let mut action = Action::Wait;
+            for intent in ai::propose(&ai.behaviours, &situation, &mut *data.rng) {
+                let proposed = match intent {
+                    Intent::Act(action) => Some(action),
+                    Intent::Approach(goal) => {
+                        step_towards(&mut data.path, &data.map, &changes, entity, wp, &goal)
+                            .map(Action::Move)
+                    }
+                    Intent::Retreat(threat) => {
+                        ai::retreat(&data.map, wp, &threat).map(Action::Move)
+                    }
+                };
+                if let Some(proposed) = proposed {
+                    action = proposed;
+                    break;
+                }
+            }
+            data.actions.send(ActionRequest {
+                actor: entity,
+                action,
+            });
+        }
+    }
+
+    fn setup(&mut self, res: &mut Resources) {
+        Self::SystemData::setup(res);
+
+        self.tile_reader = Some(res.fetch_mut::<TileChanges>().register_reader());
+        self.noise_reader = Some(res.fetch_mut::<Noises>().register_reader());
+    }
+}
+
+/// A pack takes on the target of an ally close by, along with where the
+/// ally thinks it is.
+fn pack_target(
+    data: &SystemData,
+    entity: Entity,
+    wp: &WorldPosition,
+    ai: &Ai,
+    team: Option<u32>,
+) -> Option<(Entity, WorldPosition)> {
+    let radius = ai
+        .behaviours
+        .iter()
+        .filter_map(|behaviour| match behaviour {
+            Behaviour::Pack(radius) => Some(*radius),
+            _ => None,
+        })
+        .max()?;
+    (&data.entities, &data.worldpos, &data.teams, &data.target)
+        .join()
+        .find(|(other, ally_wp, ally_team, _)| {
+            *other != entity && Some(ally_team.0) == team && ally_wp.distance(wp) <= radius
+        })
+        .map(|(_, _, _, target)| (target.target, target.last_known))
+}
+
+/// Whether the teams of the two are hostile, according to `Factions`.
+fn is_hostile(data: &SystemData, entity: Entity, other: Entity) -> bool {
+    if data.dead.contains(other) {
+        return false;
+    }
+    match (data.teams.get(entity), data.teams.get(other)) {
+        (Some(team), Some(other_team)) => data.factions.is_hostile(team.0, other_team.0),
+        _ => false,
+    }
+}
+
+/// Characters of hostile teams the monster can see, with their positions.
+fn visible_hostiles(
+    data: &SystemData,
+    entity: Entity,
+    wp: &WorldPosition,
+) -> Vec<(Entity, WorldPosition)> {
+    (&data.entities, &data.worldpos)
+        .join()
+        .filter(|(other, other_wp)| {
+            *other != entity
+                && is_hostile(data, entity, *other)
+                && other_wp.distance(wp) <= MONSTER_SIGHT_RADIUS
+                && fov::can_see(&data.map, wp, other_wp)
+        })
+        .map(|(other, other_wp)| (other, *other_wp))
+        .collect()
+}
+
+/// The next step on the way to `goal`. The path there is cached and only
+/// searched again when the goal moves or something changes along it.
+fn step_towards(
+    paths: &mut WriteStorage<CachedPath>,
+    map: &WorldMap,
+    changes: &[TileChanged],
+    entity: Entity,
+    wp: &WorldPosition,
+    goal: &WorldPosition,
+) -> Option<Direction> {
+    let stale = match paths.get_mut(entity) {
+        Some(path) => {
+            while path.steps.last() == Some(wp) {
+                path.steps.pop();
+            }
+            path.goal != *goal
+                || path.steps.is_empty()
+                || changes
+                    .iter()
+                    .any(|change| change.cause != Some(entity) && path.steps.contains(&change.pos))
+        }
+        None => true,
+    };
+
+    if stale {
+        // Characters are in the way, except for one standing on the goal.
+        let route = pathfinding::find_path(map, wp, goal, |pos, tile| match tile.character {
+            Some(other) if other != entity && pos != goal => None,
+            _ => pathfinding::terrain_cost(tile.terrain),
+        });
+        match route {
+            Some(route) => {
+                paths
+                    .insert(entity, CachedPath::new(*goal, route))
+                    .expect("Adding CachedPath failed");
+            }
+            None => {
+                paths.remove(entity);
+            }
+        }
+    }
+
+    let dir = paths
+        .get(entity)
+        .and_then(|path| path.steps.last())
+        .and_then(|next| wp.direction_to(next));
+    if dir.is_none() {
+        paths.remove(entity);
+    }
+    dir
+}
@@ -0,0 +1,58 @@
+use crate::{
+    components::{PlayerControlledCharacter, WorldPosition},
+    data::{Action, PlayerAction},
+    resources::{ActionQueue, ActionRequest, PlayerActionResource, Scheduler, WorldMap},
+};
+use amethyst::ecs::prelude::*;
+
+/// Turns the player's input into an `Action` for the player character.
+pub struct PlayerControlSystem;
+
+#[derive(SystemData)]
+pub struct SystemData<'s> {
+    control: ReadStorage<'s, PlayerControlledCharacter>,
+    action: Read<'s, PlayerActionResource>,
+    actions: Read<'s, ActionQueue>,
+    scheduler: Read<'s, Scheduler>,
+    worldpos: ReadStorage<'s, WorldPosition>,
+    map: Read<'s, WorldMap>,
+    entities: Entities<'s>,
+}
+
+impl<'s> System<'s> for PlayerControlSystem {
+    type SystemData = SystemData<'s>;
+
+    fn run(&mut self, data: Self::SystemData) {
+        for (entity, _) in (&data.entities, &data.control).join() {
+            if !data.scheduler.is_acting(entity) {
+                continue;
+            }
+            let action = match data.action.action {
+                Some(PlayerAction::Move(dir)) => Action::Move(dir),
+                Some(PlayerAction::Wait) => Action::Wait,
+                Some(PlayerAction::PickUp(index)) => Action::PickUp(index),
+                Some(PlayerAction::TakeStairs(stairs)) => Action::TakeStairs(stairs),
+                Some(PlayerAction::Use(index)) => Action::Use(index),
+                Some(PlayerAction::Drop(index)) => Action::Drop(index),
+                Some(PlayerAction::Throw(index, dir)) => Action::Throw(index, dir),
+                Some(PlayerAction::Attack(dir)) => {
+                    // `DetectPlayerActionSystem` made sure there is somebody.
+                    let target = data
+                        .worldpos
+                        .get(entity)
+                        .and_then(|pos| data.map.read(&pos.step_dir(dir)));
+                    match target {
+                        Some(target) => Action::Attack(target),
+                        None => continue,
+                    }
+                }
+                // Handled by `DetectPlayerActionSystem` and `PlayState`.
+                Some(PlayerAction::Grab) | Some(PlayerAction::Quit) | None => return,
+            };
+            data.actions.send(ActionRequest {
+                actor: entity,
+                action,
+            });
+        }
+    }
+}
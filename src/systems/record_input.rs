@@ -0,0 +1,49 @@
+use crate::{
+    replay::RecordedKey,
+    resources::{InputRecorder, PlayerActionResource},
+    tui::Key,
+};
+use amethyst::{
+    core::shrev::EventChannel,
+    ecs::{prelude::*, SystemData as _},
+};
+
+/// Feeds the keys and the resulting action of every frame to the
+/// `InputRecorder`, if there is one.
+#[derive(Default)]
+pub struct RecordInputSystem {
+    reader: Option<ReaderId<Key>>,
+}
+
+#[derive(SystemData)]
+pub struct SystemData<'s> {
+    inputs: Read<'s, EventChannel<Key>>,
+    action: Read<'s, PlayerActionResource>,
+    recorder: Option<Write<'s, InputRecorder>>,
+}
+
+impl<'s> System<'s> for RecordInputSystem {
+    type SystemData = SystemData<'s>;
+
+    fn run(&mut self, data: Self::SystemData) {
+        // Always drain the reader so a recording never starts with stale keys.
+        let keys = data
+            .inputs
+            .read(self.reader.as_mut().unwrap())
+            .filter_map(RecordedKey::from_key)
+            .collect();
+
+        if let Some(mut recorder) = data.recorder {
+            recorder.record(keys, data.action.action);
+        }
+    }
+
+    fn setup(&mut self, res: &mut Resources) {
+        Self::SystemData::setup(res);
+        self.reader = Some(
+            res.get_mut::<EventChannel<Key>>()
+                .unwrap()
+                .register_reader(),
+        );
+    }
+}
@@ -0,0 +1,596 @@
+use crate::{
+    components::{
+        Actor, Ai, Dead, Equipment, Health, Inventory, LastAction, Named,
+        PlayerControlledCharacter, Stunned, Team, WorldPosition, CARRY_CAPACITY,
+    },
+    data::{
+        Action, ActionError, Direction, ItemProperties, Relation, Stairs, StatusEffect, Terrain,
+        TileVisibility, UseEffect,
+    },
+    dungeon, fov, mapgen,
+    play::initialise_item,
+    resources::{
+        ActionQueue, Board, Factions, GameRng, LogEvents, LogLine, Noise, Noises, Scheduler,
+        TileChanged, TileChanges, WorldMap,
+    },
+    specs_ext::SpecsExt,
+    system_chain::{ArmorDefense, DamageEvent, ReifiedEventSystem, WeaponDamage},
+    tui::{Color, Style},
+};
+use amethyst::ecs::prelude::*;
+
+/// Carries out the actions in the `ActionQueue`, one after the other, so
+/// that each is checked against what the previous ones did. Actions that
+/// can't be done are reported to the actor through `LastAction`, and to
+/// the player through the log.
+pub struct ResolveActionsSystem;
+
+#[derive(SystemData)]
+pub struct SystemData<'s> {
+    actions: Write<'s, ActionQueue>,
+    scheduler: Read<'s, Scheduler>,
+    actor: WriteStorage<'s, Actor>,
+    last_action: WriteStorage<'s, LastAction>,
+    player: ReadStorage<'s, PlayerControlledCharacter>,
+    health: WriteStorage<'s, Health>,
+    dead: WriteStorage<'s, Dead>,
+    stun: WriteStorage<'s, Stunned>,
+    team: ReadStorage<'s, Team>,
+    ai: ReadStorage<'s, Ai>,
+    factions: Write<'s, Factions>,
+    name: ReadStorage<'s, Named>,
+    worldpos: WriteStorage<'s, WorldPosition>,
+    inventory: WriteStorage<'s, Inventory>,
+    equipment: ReadStorage<'s, Equipment>,
+    map: Write<'s, WorldMap>,
+    tile_changes: Write<'s, TileChanges>,
+    noises: Write<'s, Noises>,
+    rng: Write<'s, GameRng>,
+    board: Read<'s, Board>,
+    lazy: Read<'s, LazyUpdate>,
+    log: Read<'s, LogEvents>,
+    entities: Entities<'s>,
+    damage_chain: ReifiedEventSystem<'s, (WeaponDamage, ArmorDefense)>,
+}
+
+impl<'s> System<'s> for ResolveActionsSystem {
+    type SystemData = SystemData<'s>;
+
+    fn run(&mut self, mut data: Self::SystemData) {
+        let mut resolved = Vec::new();
+        while let Ok(request) = data.actions.receiver().try_recv() {
+            let actor = request.actor;
+            // One action per turn, and only for the ones whose turn it is.
+            if !data.scheduler.is_acting(actor)
+                || resolved.contains(&actor)
+                || data.dead.contains(actor)
+                || !data.entities.is_alive(actor)
+            {
+                continue;
+            }
+            resolved.push(actor);
+
+            let action = request.action;
+            let stunned = data.stun.contains(actor);
+            let result = match action {
+                // Items check for themselves, since cures work through the daze.
+                Action::Wait | Action::Use(_) => data.resolve(actor, &action),
+                _ if stunned => Err(ActionError::Stunned),
+                _ => data.resolve(actor, &action),
+            };
+
+            let is_player = data.player.contains(actor);
+            let cost = action_cost(&action, &result, is_player, stunned);
+            match &result {
+                Ok(()) => data.make_noise(actor, action.noise()),
+                Err(err) if is_player => data.log.send(err.to_string()),
+                Err(_) => {}
+            }
+            if let Some(actor) = data.actor.get_mut(actor) {
+                actor.energy -= cost;
+            }
+            data.last_action
+                .insert(actor, LastAction { action, result })
+                .ok();
+        }
+
+        // Actors that let their turn pass wait.
+        for &entity in &data.scheduler.acting {
+            if !resolved.contains(&entity) {
+                if let Some(actor) = data.actor.get_mut(entity) {
+                    actor.energy -= Action::Wait.cost();
+                }
+            }
+        }
+    }
+}
+
+/// The energy spent on an action. After a failure the player gets to try
+/// something else, unless stunned, since the daze wears off with every
+/// turn taken. Anybody else would most likely try the same thing again,
+/// so they wait instead.
+fn action_cost(
+    action: &Action,
+    result: &Result<(), ActionError>,
+    is_player: bool,
+    stunned: bool,
+) -> i32 {
+    match result {
+        Ok(()) => action.cost(),
+        Err(_) if is_player && !stunned => 0,
+        Err(_) => Action::Wait.cost(),
+    }
+}
+
+impl<'s> SystemData<'s> {
+    fn resolve(&mut self, actor: Entity, action: &Action) -> Result<(), ActionError> {
+        match *action {
+            Action::Move(dir) => self.step(actor, dir),
+            Action::Attack(target) => {
+                let position = self.position(actor)?;
+                let target_position = self
+                    .worldpos
+                    .get(target)
+                    .cloned()
+                    .ok_or(ActionError::NoTarget)?;
+                if !position.is_adjacent(&target_position) {
+                    return Err(ActionError::NotAdjacent);
+                }
+                self.attack(actor, target, None)
+            }
+            Action::Shoot(target) => {
+                let position = self.position(actor)?;
+                let target_position = self
+                    .worldpos
+                    .get(target)
+                    .cloned()
+                    .ok_or(ActionError::NoTarget)?;
+                // Only monsters with a ranged behaviour can shoot at all.
+                let range = self.ai.get(actor).and_then(Ai::range).unwrap_or(0);
+                if position.distance(&target_position) > range {
+                    return Err(ActionError::OutOfRange);
+                }
+                if !fov::can_see(&self.map, &position, &target_position) {
+                    return Err(ActionError::NotInSight);
+                }
+                self.attack(actor, target, None)
+            }
+            Action::Wait => Ok(()),
+            Action::PickUp(index) => self.pick_up(actor, index),
+            Action::Drop(index) => self.drop_item(actor, index),
+            Action::Use(index) => self.use_item(actor, index),
+            Action::Throw(index, dir) => self.throw(actor, index, dir),
+            Action::OpenDoor(dir) => self.open_door(actor, dir),
+            Action::TakeStairs(stairs) => self.take_stairs(actor, stairs),
+        }
+    }
+
+    /// Logs a message meant for the player if the actor is the player.
+    fn tell<T: Into<LogLine>>(&self, actor: Entity, message: T) {
+        if self.player.contains(actor) {
+            self.log.send(message);
+        }
+    }
+
+    fn make_noise(&mut self, actor: Entity, radius: i32) {
+        if radius <= 0 {
+            return;
+        }
+        if let Some(pos) = self.worldpos.get(actor) {
+            self.noises.single_write(Noise {
+                pos: *pos,
+                radius,
+                source: actor,
+            });
+        }
+    }
+
+    fn position(&self, actor: Entity) -> Result<WorldPosition, ActionError> {
+        self.worldpos
+            .get(actor)
+            .cloned()
+            .ok_or(ActionError::OutOfBounds)
+    }
+
+    fn step(&mut self, actor: Entity, dir: Direction) -> Result<(), ActionError> {
+        let position = self.position(actor)?;
+        let next = position.step_dir(dir);
+        let terrain = self.map.terrain(&next).ok_or(ActionError::OutOfBounds)?;
+
+        if let Some(other) = self.map.read(&next) {
+            return match self.relation(actor, other) {
+                Relation::Hostile => self.attack(actor, other, None),
+                // Monsters wait for each other to move on, the player
+                // pushes past.
+                _ if self.player.contains(actor) => {
+                    self.swap(actor, other, position, next);
+                    Ok(())
+                }
+                _ => Err(ActionError::Occupied),
+            };
+        }
+        if terrain == Terrain::ClosedDoor {
+            return self.open_door(actor, dir);
+        }
+        if !terrain.is_passable() {
+            return Err(ActionError::Blocked(terrain));
+        }
+
+        // The map is updated right away so that the following actions see
+        // the move, `ApplyWorldMapSystem` agrees with it later on.
+        self.map.get_mut(&position).unwrap().character = None;
+        self.map.get_mut(&next).unwrap().character = Some(actor);
+        self.worldpos.insert(actor, next).ok();
+
+        if self.player.contains(actor) {
+            for item in &self.map.get(&next).unwrap().items {
+                self.log
+                    .send(format!("You see a {}", item.item.description()));
+            }
+        }
+        Ok(())
+    }
+
+    /// Characters without a team are at odds with everybody.
+    fn relation(&self, a: Entity, b: Entity) -> Relation {
+        match (self.team.get(a), self.team.get(b)) {
+            (Some(a), Some(b)) => self.factions.relation(a.0, b.0),
+            _ => Relation::Hostile,
+        }
+    }
+
+    fn swap(&mut self, actor: Entity, other: Entity, from: WorldPosition, to: WorldPosition) {
+        self.map.get_mut(&from).unwrap().character = Some(other);
+        self.map.get_mut(&to).unwrap().character = Some(actor);
+        self.worldpos.insert(actor, to).ok();
+        self.worldpos.insert(other, from).ok();
+        let name = self.name.get(other).map_or("somebody", |x| &*x.name);
+        self.tell(actor, format!("You swap places with {}", name));
+    }
+
+    fn open_door(&mut self, actor: Entity, dir: Direction) -> Result<(), ActionError> {
+        let door = self.position(actor)?.step_dir(dir);
+        if self.map.terrain(&door) != Some(Terrain::ClosedDoor) {
+            return Err(ActionError::NoDoor);
+        }
+        self.map.set_terrain(&door, Terrain::OpenDoor);
+        self.tile_changes.single_write(TileChanged {
+            pos: door,
+            cause: Some(actor),
+        });
+        self.tell(actor, "You open the door");
+        Ok(())
+    }
+
+    /// Hits `target`, with `thrown` if it is given or else with whatever
+    /// `attacker` has equipped.
+    fn attack(
+        &mut self,
+        attacker: Entity,
+        target: Entity,
+        thrown: Option<ItemProperties>,
+    ) -> Result<(), ActionError> {
+        if self.dead.contains(target) || !self.entities.is_alive(target) {
+            return Err(ActionError::NoTarget);
+        }
+        if !self.health.contains(target) {
+            return Err(ActionError::NoTarget);
+        }
+        self.provoke(attacker, target);
+        let health = self.health.get_mut(target).unwrap();
+
+        // Unarmed hits do 1 damage.
+        let mut evt = DamageEvent {
+            attacker,
+            target,
+            damage: 1,
+            effects: Vec::new(),
+            thrown,
+        };
+        self.damage_chain.run(&mut evt);
+
+        health.health -= evt.damage;
+        let remaining = health.health;
+        self.log.send(LogLine::styled(
+            format!(
+                "{} (id {}) attacked {} (id {}): {} hp left",
+                self.name
+                    .get(attacker)
+                    .map(|x| &*x.name)
+                    .unwrap_or("Unknown"),
+                attacker.id(),
+                self.name.get(target).map(|x| &*x.name).unwrap_or("Unknown"),
+                target.id(),
+                remaining
+            ),
+            Style::new().fg(Color::Red),
+        ));
+
+        for effect in evt.effects {
+            match effect {
+                StatusEffect::Stun(turns) => {
+                    self.stun.get_mut_or_default(target).time += turns;
+                }
+            }
+        }
+
+        if remaining <= 0 {
+            self.kill(target);
+        }
+        Ok(())
+    }
+
+    /// Attacking a character that isn't hostile yet turns its whole team
+    /// against the attacker's.
+    fn provoke(&mut self, attacker: Entity, target: Entity) {
+        let (team, target_team) = match (self.team.get(attacker), self.team.get(target)) {
+            (Some(a), Some(b)) if a.0 != b.0 => (a.0, b.0),
+            _ => return,
+        };
+        if self.factions.is_hostile(team, target_team) {
+            return;
+        }
+        self.factions.set(team, target_team, Relation::Hostile);
+        self.log.send(LogLine::styled(
+            format!(
+                "The {} turn hostile towards the {}!",
+                self.factions.name(target_team),
+                self.factions.name(team)
+            ),
+            Style::new().fg(Color::Red).bold(),
+        ));
+    }
+
+    /// Drops everything the target carries and replaces it with a new
+    /// enemy somewhere on the level.
+    fn kill(&mut self, target: Entity) {
+        self.dead.insert(target, Dead).ok();
+        let position = *self.worldpos.get(target).unwrap();
+        let tile = self.map.get_mut(&position).unwrap();
+        let mut itemc = 0;
+        tile.character = None;
+        self.tile_changes.single_write(TileChanged {
+            pos: position,
+            cause: Some(target),
+        });
+        let carried = self.inventory.get(target).map(|x| x.items.iter());
+        let worn = self
+            .equipment
+            .get(target)
+            .map(|x| x.items.iter().map(|(_, item)| item));
+        for item in carried
+            .into_iter()
+            .flatten()
+            .chain(worn.into_iter().flatten())
+        {
+            initialise_item(
+                self.lazy.create_entity(&self.entities),
+                self.board.0.unwrap(),
+                position,
+                item.clone(),
+            )
+            .build();
+            itemc += 1;
+        }
+        self.entities.delete(target).ok();
+        self.log.send(LogLine::styled(
+            format!(
+                "{} (id {}) died{}",
+                self.name.get(target).map(|x| &*x.name).unwrap_or("Unknown"),
+                target.id(),
+                if itemc == 0 {
+                    "".to_owned()
+                } else if itemc == 1 {
+                    " and dropped 1 item".to_owned()
+                } else {
+                    format!(" and dropped {} items", itemc)
+                }
+            ),
+            Style::new().fg(Color::Yellow).bold(),
+        ));
+        self.lazy.exec_mut(move |world| {
+            crate::play::initialise_enemy(world);
+        });
+        self.log.send("New enemy appears!");
+    }
+
+    fn pick_up(&mut self, actor: Entity, index: usize) -> Result<(), ActionError> {
+        let position = self.position(actor)?;
+        let inventory = self.inventory.get_mut(actor).ok_or(ActionError::NoItem)?;
+        let tile = match self.map.get_mut(&position) {
+            Some(tile) if index < tile.items.len() => tile,
+            _ => return Err(ActionError::NoItem),
+        };
+        let carried = inventory.weight() + self.equipment.get(actor).map_or(0, |x| x.weight());
+        if carried + tile.items[index].item.weight() > CARRY_CAPACITY {
+            return Err(ActionError::TooHeavy {
+                item: tile.items[index].item.description(),
+                carried,
+            });
+        }
+        let item = tile.items.remove(index);
+        self.entities.delete(item.entity).ok();
+        let message = format!("Grabbed {}", item.item.description());
+        inventory.add(item.item);
+        self.tell(actor, message);
+        Ok(())
+    }
+
+    fn drop_item(&mut self, actor: Entity, index: usize) -> Result<(), ActionError> {
+        let position = self.position(actor)?;
+        let inventory = self.inventory.get_mut(actor).ok_or(ActionError::NoItem)?;
+        if index >= inventory.items.len() {
+            return Err(ActionError::NoItem);
+        }
+        // Stacks are dropped whole.
+        let item = inventory.items.remove(index);
+        self.tell(actor, format!("You drop {}", item.description()));
+        initialise_item(
+            self.lazy.create_entity(&self.entities),
+            self.board.0.unwrap(),
+            position,
+            item,
+        )
+        .build();
+        Ok(())
+    }
+
+    fn use_item(&mut self, actor: Entity, index: usize) -> Result<(), ActionError> {
+        let inventory = self.inventory.get_mut(actor).ok_or(ActionError::NoItem)?;
+        let props = match inventory.items.get(index) {
+            Some(item) if item.properties().is_usable() => item.properties(),
+            Some(item) => return Err(ActionError::NotUsable(item.description())),
+            None => return Err(ActionError::NoItem),
+        };
+        // Cures are the one thing that can be used while stunned.
+        if self.stun.contains(actor) && !props.on_use.contains(&UseEffect::Cure) {
+            return Err(ActionError::Stunned);
+        }
+        let mut item = inventory.take(index);
+        if !item.use_charge() {
+            inventory.add(item);
+        }
+        self.tell(actor, format!("You use {}", props.full_name()));
+
+        if let (Some(amount), Some(health)) = (props.heal, self.health.get_mut(actor)) {
+            let healed = health.heal(amount);
+            let message = format!(
+                "You feel better (+{} hp, {} hp left)",
+                healed, health.health
+            );
+            self.tell(actor, message);
+        }
+
+        for effect in props.on_use {
+            match effect {
+                UseEffect::Apply(StatusEffect::Stun(turns)) => {
+                    self.stun.get_mut_or_default(actor).time += turns;
+                    self.tell(actor, "Your head spins");
+                }
+                UseEffect::Cure => {
+                    if self.stun.remove(actor).is_some() {
+                        self.tell(actor, "Your head clears");
+                    }
+                }
+                UseEffect::Teleport => {
+                    let current = self.worldpos.get(actor).cloned();
+                    let target = mapgen::find_spawn(&self.map, &mut *self.rng, current.as_ref());
+                    match target {
+                        Some(target) => {
+                            self.worldpos.insert(actor, target).ok();
+                            self.tell(actor, "The world blinks around you");
+                        }
+                        None => self.tell(actor, "Nothing happens"),
+                    }
+                }
+                UseEffect::RevealMap => {
+                    // Only the player has a map to reveal.
+                    if self.player.contains(actor) {
+                        for tile in self.map.tiles.iter_mut().flatten() {
+                            if tile.visibility == TileVisibility::Unseen {
+                                tile.visibility = TileVisibility::Remembered;
+                            }
+                        }
+                        self.log.send("The layout of the level becomes clear");
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn throw(&mut self, actor: Entity, index: usize, dir: Direction) -> Result<(), ActionError> {
+        let position = self.position(actor)?;
+        let inventory = self.inventory.get_mut(actor).ok_or(ActionError::NoItem)?;
+        if index >= inventory.items.len() {
+            return Err(ActionError::NoItem);
+        }
+        let item = inventory.take(index);
+        self.tell(actor, format!("You throw {}", item.description()));
+
+        // The item flies until the next tile is a wall or closed door,
+        // or until it hits a character, and lands where it stopped.
+        let mut landing = position;
+        loop {
+            let next = landing.step_dir(dir);
+            match self.map.terrain(&next) {
+                Some(terrain) if !terrain.is_opaque() => landing = next,
+                _ => break,
+            }
+            if let Some(target) = self.map.read(&landing) {
+                // Missing a dead target still uses up the throw.
+                self.attack(actor, target, Some(item.properties())).ok();
+                break;
+            }
+        }
+
+        initialise_item(
+            self.lazy.create_entity(&self.entities),
+            self.board.0.unwrap(),
+            landing,
+            item,
+        )
+        .build();
+        Ok(())
+    }
+
+    fn take_stairs(&mut self, actor: Entity, stairs: Stairs) -> Result<(), ActionError> {
+        // Only the player changes levels, everybody else stays put.
+        if !self.player.contains(actor)
+            || self.map.terrain(&self.position(actor)?) != Some(stairs.terrain())
+        {
+            return Err(ActionError::NoStairs(stairs));
+        }
+        let down = stairs == Stairs::Down;
+        // The level is swapped once the turn is over, since that needs the
+        // whole world.
+        self.lazy
+            .exec_mut(move |world| dungeon::change_level(world, down));
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn failures_cost_nothing_for_the_player_only() {
+        let action = Action::Use(0);
+        let failed = Err(ActionError::NotUsable("shoe".to_owned()));
+        assert_eq!(action_cost(&action, &failed, true, false), 0);
+        assert_eq!(
+            action_cost(&action, &failed, false, false),
+            Action::Wait.cost()
+        );
+        assert_eq!(action_cost(&action, &Ok(()), true, false), action.cost());
+    }
+
+    #[test]
+    fn stunned_player_loses_the_turn_on_failure() {
+        // Otherwise failing over and over would wear the stun off for free.
+        let failed = Err(ActionError::NotUsable("shoe".to_owned()));
+        assert_eq!(
+            action_cost(&Action::Use(0), &failed, true, true),
+            Action::Wait.cost()
+        );
+        assert_eq!(
+            action_cost(&Action::Use(0), &Err(ActionError::NoItem), true, true),
+            Action::Wait.cost()
+        );
+        assert_eq!(
+            action_cost(
+                &Action::Move(Direction::Up),
+                &Err(ActionError::Stunned),
+                true,
+                true
+            ),
+            Action::Wait.cost()
+        );
+        // A cure that works costs as much as any other use.
+        assert_eq!(
+            action_cost(&Action::Use(0), &Ok(()), true, true),
+            Action::Use(0).cost()
+        );
+    }
+}
@@ -0,0 +1,158 @@
+use crate::{
+    components::{Actor, Dead, PlayerControlledCharacter},
+    resources::{GameRng, PlayerActionResource, Scheduler},
+};
+use amethyst::ecs::prelude::*;
+
+/// Decides who acts in this step of the tick dispatcher. If nobody is
+/// ready, time is advanced until somebody is. The player only acts once
+/// there is an action for them. Only the entities in `Scheduler::acting`
+/// submit actions, which pay for them in `ResolveActionsSystem`.
+pub struct SchedulerSystem;
+
+#[derive(SystemData)]
+pub struct SystemData<'s> {
+    scheduler: Write<'s, Scheduler>,
+    actor: WriteStorage<'s, Actor>,
+    player: ReadStorage<'s, PlayerControlledCharacter>,
+    dead: ReadStorage<'s, Dead>,
+    action: Read<'s, PlayerActionResource>,
+    rng: Write<'s, GameRng>,
+    entities: Entities<'s>,
+}
+
+impl<'s> System<'s> for SchedulerSystem {
+    type SystemData = SystemData<'s>;
+
+    fn run(&mut self, mut data: Self::SystemData) {
+        data.scheduler.acting.clear();
+        data.scheduler.step += 1;
+        data.rng.reseed_for_step(data.scheduler.step);
+
+        let ticks = (&data.actor, !&data.dead)
+            .join()
+            .filter_map(|(actor, ())| actor.ticks_until_ready())
+            .min();
+        let ticks = match ticks {
+            Some(ticks) => ticks,
+            None => return,
+        };
+        if ticks > 0 {
+            data.scheduler.time += ticks;
+            for (actor, ()) in (&mut data.actor, !&data.dead).join() {
+                actor.energy += actor.speed * ticks as i32;
+            }
+        }
+
+        // The player acts on their own, so that the others react to the
+        // outcome. Monsters that are ready at the same time act next step.
+        if data.action.action.is_some() {
+            for (entity, actor, _, ()) in
+                (&data.entities, &data.actor, &data.player, !&data.dead).join()
+            {
+                if actor.is_ready() {
+                    data.scheduler.acting.push(entity);
+                }
+            }
+            if !data.scheduler.acting.is_empty() {
+                return;
+            }
+        }
+
+        for (entity, actor, (), ()) in
+            (&data.entities, &data.actor, !&data.player, !&data.dead).join()
+        {
+            if actor.is_ready() {
+                data.scheduler.acting.push(entity);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        components::NORMAL_SPEED,
+        data::{Action, PlayerAction},
+    };
+
+    /// Lets a normal speed player and monsters of the given speeds wait
+    /// whenever it's their turn, and counts how often each monster acts
+    /// during `turns` turns of the player. Counting starts at the player's
+    /// second turn, once everybody has fallen into step.
+    fn actions_per_player_turns(speeds: &[i32], turns: u32) -> Vec<u32> {
+        let mut world = World::new();
+        let mut system = SchedulerSystem;
+        System::setup(&mut system, &mut world.res);
+        world.write_resource::<PlayerActionResource>().action = Some(PlayerAction::Wait);
+
+        let player = world
+            .create_entity()
+            .with(Actor::new(NORMAL_SPEED))
+            .with(PlayerControlledCharacter)
+            .build();
+        let monsters: Vec<Entity> = speeds
+            .iter()
+            .map(|&speed| world.create_entity().with(Actor::new(speed)).build())
+            .collect();
+
+        let mut counts = vec![0; speeds.len()];
+        let mut start = Vec::new();
+        let mut player_turns = 0;
+        while player_turns < turns + 2 {
+            system.run_now(&world.res);
+            let acting = world.read_resource::<Scheduler>().acting.clone();
+            assert!(!acting.is_empty(), "nobody acted");
+            for entity in acting {
+                let mut actors = world.write_storage::<Actor>();
+                actors.get_mut(entity).unwrap().energy -= Action::Wait.cost();
+                if entity == player {
+                    player_turns += 1;
+                    if player_turns == 2 {
+                        start = counts.clone();
+                    }
+                } else {
+                    let index = monsters.iter().position(|x| *x == entity).unwrap();
+                    counts[index] += 1;
+                }
+            }
+        }
+        counts
+            .iter()
+            .zip(start)
+            .map(|(end, start)| end - start)
+            .collect()
+    }
+
+    #[test]
+    fn speed_decides_how_often_actors_act() {
+        // Twice, once and every other turn of the player.
+        assert_eq!(actions_per_player_turns(&[20, 10, 5], 8), vec![16, 8, 4]);
+    }
+
+    #[test]
+    fn player_acts_alone() {
+        let mut world = World::new();
+        let mut system = SchedulerSystem;
+        System::setup(&mut system, &mut world.res);
+        world.write_resource::<PlayerActionResource>().action = Some(PlayerAction::Wait);
+        let player = world
+            .create_entity()
+            .with(Actor::new(NORMAL_SPEED))
+            .with(PlayerControlledCharacter)
+            .build();
+        let monster = world.create_entity().with(Actor::new(NORMAL_SPEED)).build();
+
+        system.run_now(&world.res);
+        assert_eq!(world.read_resource::<Scheduler>().acting, vec![player]);
+
+        world
+            .write_storage::<Actor>()
+            .get_mut(player)
+            .unwrap()
+            .energy -= Action::Wait.cost();
+        system.run_now(&world.res);
+        assert_eq!(world.read_resource::<Scheduler>().acting, vec![monster]);
+    }
+}
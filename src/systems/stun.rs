@@ -1,4 +1,4 @@
-use crate::components::Stunned;
+use crate::{components::Stunned, resources::Scheduler};
 use amethyst::ecs::prelude::*;
 
 pub struct StunSystem;
@@ -6,6 +6,7 @@ pub struct StunSystem;
 #[derive(SystemData)]
 pub struct SystemData<'s> {
     stunned: WriteStorage<'s, Stunned>,
+    scheduler: Read<'s, Scheduler>,
     entities: Entities<'s>,
 }
 
@@ -14,7 +15,11 @@ impl<'s> System<'s> for StunSystem {
 
     fn run(&mut self, mut data: Self::SystemData) {
         let mut cleared = Vec::new();
+        // Stuns last for a number of the stunned character's own turns.
         for (entity, stun) in (&data.entities, &mut data.stunned).join() {
+            if !data.scheduler.is_acting(entity) {
+                continue;
+            }
             stun.time -= 1;
             if stun.time == 0 {
                 cleared.push(entity);
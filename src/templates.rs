@@ -0,0 +1,174 @@
+use std::{error::Error, fmt, fs, io, path::Path};
+
+use rand::{seq::SliceRandom, Rng};
+
+use crate::{
+    components::{Item, NORMAL_SPEED},
+    data::{Behaviour, ItemPart},
+    tui::Color,
+};
+
+pub const MONSTERS_PATH: &str = "resources/monsters.ron";
+pub const ITEMS_PATH: &str = "resources/items.ron";
+pub const FACTIONS_PATH: &str = "resources/factions.ron";
+
+#[derive(Debug)]
+pub enum TemplateError {
+    Io(String, io::Error),
+    Parse(String, ron::de::Error),
+    UnknownItem { monster: String, item: String },
+    UnknownTeam { monster: String, team: u32 },
+}
+
+impl fmt::Display for TemplateError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TemplateError::Io(path, err) => write!(f, "Could not read {}: {}", path, err),
+            TemplateError::Parse(path, err) => write!(f, "Could not parse {}: {}", path, err),
+            TemplateError::UnknownItem { monster, item } => {
+                write!(f, "Monster {} refers to unknown item {}", monster, item)
+            }
+            TemplateError::UnknownTeam { monster, team } => {
+                write!(f, "Monster {} belongs to unknown team {}", monster, team)
+            }
+        }
+    }
+}
+
+impl Error for TemplateError {}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct MonsterTemplate {
+    pub id: String,
+    pub name: String,
+    pub glyph: String,
+    #[serde(default = "default_monster_color")]
+    pub color: Color,
+    pub health: i32,
+    /// See `components::NORMAL_SPEED`.
+    #[serde(default = "default_monster_speed")]
+    pub speed: i32,
+    /// Id of a `FactionTemplate`.
+    pub team: u32,
+    #[serde(default = "default_behaviours")]
+    pub behaviours: Vec<Behaviour>,
+    /// Item template ids. The monster carries one of them, picked by spawn weight.
+    #[serde(default)]
+    pub loot: Vec<String>,
+    #[serde(default = "default_spawn_weight")]
+    pub spawn_weight: u32,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct ItemTemplate {
+    pub id: String,
+    pub parts: Vec<ItemPart>,
+    #[serde(default = "default_spawn_weight")]
+    pub spawn_weight: u32,
+}
+
+/// A team and how it gets along with the others at the start of a game.
+/// Relations go both ways, teams that aren't listed are neutral.
+#[derive(Debug, Clone, Deserialize)]
+pub struct FactionTemplate {
+    pub id: u32,
+    pub name: String,
+    #[serde(default)]
+    pub hostile: Vec<u32>,
+    #[serde(default)]
+    pub allied: Vec<u32>,
+}
+
+impl ItemTemplate {
+    pub fn build(&self) -> Item {
+        Item::new(self.parts.iter().cloned())
+    }
+}
+
+fn default_monster_color() -> Color {
+    Color::Red
+}
+
+fn default_behaviours() -> Vec<Behaviour> {
+    vec![Behaviour::Hunt, Behaviour::Wander]
+}
+
+fn default_monster_speed() -> i32 {
+    NORMAL_SPEED
+}
+
+fn default_spawn_weight() -> u32 {
+    1
+}
+
+/// All monster, item and faction definitions, loaded from `resources/`.
+#[derive(Default, Debug, Clone)]
+pub struct Templates {
+    pub monsters: Vec<MonsterTemplate>,
+    pub items: Vec<ItemTemplate>,
+    pub factions: Vec<FactionTemplate>,
+}
+
+impl Templates {
+    pub fn load() -> Result<Templates, TemplateError> {
+        let templates = Templates {
+            monsters: load_file(MONSTERS_PATH)?,
+            items: load_file(ITEMS_PATH)?,
+            factions: load_file(FACTIONS_PATH)?,
+        };
+
+        for monster in &templates.monsters {
+            if !templates.factions.iter().any(|x| x.id == monster.team) {
+                return Err(TemplateError::UnknownTeam {
+                    monster: monster.id.clone(),
+                    team: monster.team,
+                });
+            }
+            for item in &monster.loot {
+                if templates.item(item).is_none() {
+                    return Err(TemplateError::UnknownItem {
+                        monster: monster.id.clone(),
+                        item: item.clone(),
+                    });
+                }
+            }
+        }
+
+        Ok(templates)
+    }
+
+    pub fn monster(&self, id: &str) -> Option<&MonsterTemplate> {
+        self.monsters.iter().find(|x| x.id == id)
+    }
+
+    pub fn item(&self, id: &str) -> Option<&ItemTemplate> {
+        self.items.iter().find(|x| x.id == id)
+    }
+
+    pub fn random_monster<R: Rng>(&self, rng: &mut R) -> Option<&MonsterTemplate> {
+        self.monsters.choose_weighted(rng, |x| x.spawn_weight).ok()
+    }
+
+    /// Picks one of the monster's loot items, weighted by their spawn weights.
+    pub fn random_loot<R: Rng>(
+        &self,
+        monster: &MonsterTemplate,
+        rng: &mut R,
+    ) -> Option<&ItemTemplate> {
+        let items = monster
+            .loot
+            .iter()
+            .filter_map(|id| self.item(id))
+            .collect::<Vec<_>>();
+        items.choose_weighted(rng, |x| x.spawn_weight).ok().cloned()
+    }
+}
+
+fn load_file<T>(path: &str) -> Result<Vec<T>, TemplateError>
+where
+    T: for<'de> serde::Deserialize<'de>,
+{
+    let source = fs::read_to_string(Path::new(path))
+        .map_err(|err| TemplateError::Io(path.to_owned(), err))?;
+    ron::de::from_str(&source).map_err(|err| TemplateError::Parse(path.to_owned(), err))
+}
@@ -0,0 +1,194 @@
+use std::{cell::RefCell, collections::VecDeque, rc::Rc};
+
+use easycurses::{ColorPair, CursorVisibility, EasyCurses, InputMode, TimeoutMode};
+use pancurses::{A_DIM, A_REVERSE};
+
+use super::{input::Key, text::graphemes, Color, Style};
+
+/// Where `TuiRenderSystem` draws to and `TuiInputSystem` reads from.
+pub trait Backend {
+    /// Called once by `TuiRenderSystem::setup`.
+    fn init(&mut self) {}
+    /// Returns `(width, height)`.
+    fn size(&self) -> (i32, i32);
+    fn move_rc(&mut self, row: i32, col: i32);
+    /// Style used by the following prints.
+    fn set_style(&mut self, style: Style);
+    fn print(&mut self, text: &str);
+    fn refresh(&mut self) {}
+    /// All input that arrived since the last call.
+    fn poll_input(&mut self) -> Vec<Key>;
+}
+
+pub type SharedBackend = Rc<RefCell<dyn Backend>>;
+
+pub struct CursesBackend {
+    easy: EasyCurses,
+}
+
+impl CursesBackend {
+    pub fn initialize() -> Self {
+        CursesBackend {
+            easy: EasyCurses::initialize_system().unwrap(),
+        }
+    }
+}
+
+impl Backend for CursesBackend {
+    fn init(&mut self) {
+        let easy = &mut self.easy;
+        easy.set_cursor_visibility(CursorVisibility::Invisible);
+        easy.set_echo(false);
+        easy.set_keypad_enabled(true);
+        easy.set_input_mode(InputMode::Character);
+        easy.set_input_timeout(TimeoutMode::Immediate);
+        easy.set_scrolling(true);
+        easy.set_color_pair(ColorPair::new(
+            easycurses::Color::White,
+            easycurses::Color::Black,
+        ));
+    }
+
+    fn size(&self) -> (i32, i32) {
+        let (height, width) = self.easy.get_row_col_count();
+        (width, height)
+    }
+
+    fn move_rc(&mut self, row: i32, col: i32) {
+        self.easy.move_rc(row, col);
+    }
+
+    fn set_style(&mut self, style: Style) {
+        self.easy.set_color_pair(ColorPair::new(
+            curses_color(style.fg),
+            curses_color(style.bg),
+        ));
+        self.easy.set_bold(style.bold);
+        for &(attribute, on) in [(A_REVERSE, style.reverse), (A_DIM, style.dim)].iter() {
+            if on {
+                self.easy.win.attron(attribute);
+            } else {
+                self.easy.win.attroff(attribute);
+            }
+        }
+    }
+
+    fn print(&mut self, text: &str) {
+        self.easy.print(text);
+    }
+
+    fn refresh(&mut self) {
+        self.easy.refresh();
+    }
+
+    fn poll_input(&mut self) -> Vec<Key> {
+        self.easy.get_input().into_iter().collect()
+    }
+}
+
+fn curses_color(color: Color) -> easycurses::Color {
+    match color {
+        Color::Black => easycurses::Color::Black,
+        Color::Red => easycurses::Color::Red,
+        Color::Green => easycurses::Color::Green,
+        Color::Yellow => easycurses::Color::Yellow,
+        Color::Blue => easycurses::Color::Blue,
+        Color::Magenta => easycurses::Color::Magenta,
+        Color::Cyan => easycurses::Color::Cyan,
+        Color::White => easycurses::Color::White,
+    }
+}
+
+/// Draws into a grid of cells and reads input from a script, one batch of
+/// keys per frame. Used when there is no terminal. Like in `TuiRenderSystem`,
+/// the second cell of a wide grapheme holds an empty string.
+pub struct MemoryBackend {
+    width: i32,
+    height: i32,
+    grid: Vec<Vec<(String, Style)>>,
+    cursor: (i32, i32),
+    style: Style,
+    script: VecDeque<Vec<Key>>,
+}
+
+impl MemoryBackend {
+    pub fn new(width: i32, height: i32) -> Self {
+        MemoryBackend {
+            width,
+            height,
+            grid: vec![vec![(" ".to_owned(), Style::default()); width as usize]; height as usize],
+            cursor: (0, 0),
+            style: Style::default(),
+            script: VecDeque::new(),
+        }
+    }
+
+    /// Queues the keys of one more frame.
+    pub fn push_frame(&mut self, keys: Vec<Key>) {
+        self.script.push_back(keys);
+    }
+
+    /// Queues one key per frame, with `gap` empty frames after each so
+    /// that held-key throttling doesn't swallow any of them.
+    pub fn push_keys<I: IntoIterator<Item = Key>>(&mut self, keys: I, gap: usize) {
+        for key in keys {
+            self.push_frame(vec![key]);
+            for _ in 0..gap {
+                self.push_frame(Vec::new());
+            }
+        }
+    }
+
+    /// The screen contents, one string per row.
+    pub fn rows(&self) -> Vec<String> {
+        self.grid
+            .iter()
+            .map(|row| row.iter().map(|cell| cell.0.as_str()).collect())
+            .collect()
+    }
+
+    /// The grapheme and style of one cell, or `None` outside the screen.
+    #[cfg(test)]
+    pub fn cell(&self, x: i32, y: i32) -> Option<(String, Style)> {
+        self.grid
+            .get(y as usize)
+            .and_then(|row| row.get(x as usize))
+            .cloned()
+    }
+}
+
+impl Backend for MemoryBackend {
+    fn size(&self) -> (i32, i32) {
+        (self.width, self.height)
+    }
+
+    fn move_rc(&mut self, row: i32, col: i32) {
+        self.cursor = (row, col);
+    }
+
+    fn print(&mut self, text: &str) {
+        let (row, mut col) = self.cursor;
+        for (grapheme, width) in graphemes(text) {
+            for offset in 0..width as i32 {
+                let glyph = if offset == 0 { grapheme } else { "" };
+                if let Some(cell) = self
+                    .grid
+                    .get_mut(row as usize)
+                    .and_then(|r| r.get_mut((col + offset) as usize))
+                {
+                    *cell = (glyph.to_owned(), self.style);
+                }
+            }
+            col += width as i32;
+        }
+        self.cursor = (row, col);
+    }
+
+    fn set_style(&mut self, style: Style) {
+        self.style = style;
+    }
+
+    fn poll_input(&mut self) -> Vec<Key> {
+        self.script.pop_front().unwrap_or_default()
+    }
+}
@@ -1,4 +1,4 @@
-use super::{ParentHierarchy, Position, TextBlock};
+use super::{ParentHierarchy, Position, Style, TextBlock};
 use crate::specs_ext::{ComponentEventReader, SpecsExt};
 use amethyst::ecs::{prelude::*, SystemData as _};
 use hibitset::BitSetLike;
@@ -15,6 +15,7 @@ pub struct Border {
     pub cross_top_right: bool,
     pub cross_bottom_left: bool,
     pub cross_bottom_right: bool,
+    pub style: Style,
 }
 
 #[allow(dead_code)]
@@ -42,6 +43,10 @@ impl Border {
         }
     }
 
+    pub fn style(self, style: Style) -> Self {
+        Self { style, ..self }
+    }
+
     pub fn sides(self, left: bool, right: bool, top: bool, bottom: bool) -> Self {
         Self {
             left,
@@ -121,42 +126,40 @@ impl<'s> System<'s> for BorderSystem {
                 .text_block
                 .get_mut(entity)
                 .expect("Border entity has no TextBlock");
+            block.style = border.style;
+            if block.width <= 0 || block.height <= 0 {
+                block.rows.clear();
+                continue;
+            }
 
             let mut rows: Vec<Vec<char>> = (0..block.height)
                 .map(|_| " ".repeat(block.width as usize).chars().collect())
                 .collect();
 
+            let (last_x, last_y) = (block.width as usize - 1, block.height as usize - 1);
             if border.left {
-                for y in 0..block.height as usize {
-                    rows[y][0] = '|';
+                for row in rows.iter_mut() {
+                    row[0] = '|';
                 }
             }
             if border.right {
-                for y in 0..block.height as usize {
-                    rows[y][block.width as usize - 1] = '|';
+                for row in rows.iter_mut() {
+                    row[last_x] = '|';
                 }
             }
 
-            if border.top {
-                for x in 0..block.width as usize {
-                    rows[0][x] = '-';
-                }
-                if border.left {
-                    rows[0][0] = '+';
-                }
-                if border.right {
-                    rows[0][block.width as usize - 1] = '+';
+            for &(horizontal, y) in [(border.top, 0), (border.bottom, last_y)].iter() {
+                if !horizontal {
+                    continue;
                 }
-            }
-            if border.bottom {
-                for x in 0..block.width as usize {
-                    rows[block.height as usize - 1][x] = '-';
+                for cell in rows[y].iter_mut() {
+                    *cell = '-';
                 }
                 if border.left {
-                    rows[block.height as usize - 1][0] = '+';
+                    rows[y][0] = '+';
                 }
                 if border.right {
-                    rows[block.height as usize - 1][block.width as usize - 1] = '+';
+                    rows[y][last_x] = '+';
                 }
             }
 
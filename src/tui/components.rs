@@ -1,3 +1,4 @@
+use super::{style::Style, text::display_width};
 use amethyst::ecs::prelude::*;
 use std::ops::Add;
 
@@ -60,6 +61,9 @@ impl Component for GlobalPosition {
 #[derive(Default, Debug, Clone, PartialEq)]
 pub struct TextBlock {
     pub rows: Vec<String>,
+    /// Per-cell styles, indexed by row and column. Cells without one use `style`.
+    pub styles: Vec<Vec<Style>>,
+    pub style: Style,
     pub width: i32,
     pub height: i32,
 }
@@ -73,18 +77,32 @@ impl TextBlock {
     {
         TextBlock {
             rows: rows.into_iter().map(|x| x.into()).collect(),
+            styles: Vec::new(),
+            style: Style::default(),
             width,
             height,
         }
     }
 
+    pub fn with_style(self, style: Style) -> Self {
+        TextBlock { style, ..self }
+    }
+
+    pub fn style_at(&self, x: usize, y: usize) -> Style {
+        self.styles
+            .get(y)
+            .and_then(|row| row.get(x))
+            .cloned()
+            .unwrap_or(self.style)
+    }
+
     pub fn single_row<T>(text: T) -> Self
     where
         T: Into<String>,
     {
         let string = text.into();
-        let len = string.len();
-        TextBlock::new(vec![string], len as i32, 1)
+        let width = display_width(&string);
+        TextBlock::new(vec![string], width as i32, 1)
     }
 
     pub fn empty(width: i32, height: i32) -> Self {
@@ -1,18 +1,16 @@
 use amethyst::{core::shrev::EventChannel, ecs::prelude::*};
 
-use super::render::ScreenSize;
-use easycurses::EasyCurses;
-use std::{cell::RefCell, rc::Rc};
+use super::{backend::SharedBackend, render::ScreenSize};
 
 pub use easycurses::Input as Key;
 
 pub struct TuiInputSystem {
-    easy: Rc<RefCell<EasyCurses>>,
+    backend: SharedBackend,
 }
 
 impl TuiInputSystem {
-    pub fn new(easy: Rc<RefCell<EasyCurses>>) -> Self {
-        TuiInputSystem { easy }
+    pub fn new(backend: SharedBackend) -> Self {
+        TuiInputSystem { backend }
     }
 }
 
@@ -27,14 +25,14 @@ impl<'s> System<'s> for TuiInputSystem {
 
     fn run(&mut self, mut data: Self::SystemData) {
         let mut keys = Vec::new();
-        let mut easy = self.easy.borrow_mut();
-        for input in easy.get_input() {
+        let mut backend = self.backend.borrow_mut();
+        for input in backend.poll_input() {
             match input {
-                Key::Character(_) => {
+                Key::Character(_) | Key::KeyPPage | Key::KeyNPage => {
                     keys.push(input);
                 }
                 Key::KeyResize => {
-                    let (height, width) = easy.get_row_col_count();
+                    let (width, height) = backend.size();
 
                     data.screen_size.width = width;
                     data.screen_size.height = height;
@@ -1,3 +1,4 @@
+pub mod backend;
 pub mod blink;
 pub mod border;
 pub mod centering;
@@ -6,14 +7,20 @@ pub mod event;
 pub mod input;
 pub mod render;
 pub mod stacking;
+pub mod style;
+pub mod text;
+pub mod viewport;
 pub mod visibility_relation;
 
 pub use self::{
+    backend::{Backend, CursesBackend, MemoryBackend, SharedBackend},
     border::Border,
     components::*,
     event::{TuiChannel, TuiEvent},
     input::Key,
     render::{Parent, ParentHierarchy, ScreenSize},
+    style::{Color, Style},
+    viewport::Viewport,
     visibility_relation::VisibleIfChildIs,
 };
 
@@ -24,23 +31,21 @@ use self::{
     input::TuiInputSystem,
     render::{BlinkSystem, TuiRenderSystem},
     stacking::StackingSystem,
+    viewport::ViewportSystem,
     visibility_relation::VisibilityRelationSystem,
 };
 
-use std::{cell::RefCell, rc::Rc};
-
 use amethyst::{core::SystemBundle, ecs::DispatcherBuilder};
-use easycurses::EasyCurses;
 use specs_hierarchy::HierarchySystem;
 
-#[derive(Default)]
 pub struct TuiBundle<'a> {
     dep: &'a [&'a str],
+    backend: SharedBackend,
 }
 
 impl<'a> TuiBundle<'a> {
-    pub fn new() -> Self {
-        Default::default()
+    pub fn new(backend: SharedBackend) -> Self {
+        TuiBundle { dep: &[], backend }
     }
 
     #[allow(dead_code)]
@@ -52,7 +57,6 @@ impl<'a> TuiBundle<'a> {
 
 impl<'a, 'b, 'c> SystemBundle<'a, 'b> for TuiBundle<'c> {
     fn build(self, builder: &mut DispatcherBuilder<'a, 'b>) -> amethyst::core::Result<()> {
-        let easy = Rc::new(RefCell::new(EasyCurses::initialize_system().unwrap()));
         builder.add(
             HierarchySystem::<Parent>::new(),
             "parent_hierarchy_system",
@@ -83,9 +87,99 @@ impl<'a, 'b, 'c> SystemBundle<'a, 'b> for TuiBundle<'c> {
             "tui_border_system",
             &["tui_stacking_system"],
         );
+        builder.add(
+            ViewportSystem::default(),
+            "tui_viewport_system",
+            &["tui_border_system"],
+        );
         builder.add(BlinkSystem::new(), "blink_system", self.dep);
-        builder.add_thread_local(TuiRenderSystem::new(easy.clone()));
-        builder.add_thread_local(TuiInputSystem::new(easy));
+        builder.add_thread_local(TuiRenderSystem::new(self.backend.clone()));
+        builder.add_thread_local(TuiInputSystem::new(self.backend));
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::{cell::RefCell, rc::Rc};
+
+    use amethyst::{
+        core::{shrev::EventChannel, timing::Time, SystemBundle},
+        ecs::{Dispatcher, DispatcherBuilder},
+        prelude::*,
+    };
+
+    use super::*;
+    use crate::resources::LogEvents;
+
+    fn setup(backend: &Rc<RefCell<MemoryBackend>>) -> (World, Dispatcher<'static, 'static>) {
+        let mut world = World::new();
+        world.add_resource(Time::default());
+        world.add_resource(LogEvents::default());
+
+        let shared: SharedBackend = backend.clone();
+        let mut builder = DispatcherBuilder::new();
+        TuiBundle::new(shared)
+            .build(&mut builder)
+            .expect("Failed to build the tui dispatcher");
+        let mut dispatcher = builder.build();
+        dispatcher.setup(&mut world.res);
+        (world, dispatcher)
+    }
+
+    fn run_frames(world: &mut World, dispatcher: &mut Dispatcher, frames: usize) {
+        for _ in 0..frames {
+            dispatcher.dispatch(&world.res);
+            world.maintain();
+        }
+    }
+
+    #[test]
+    fn renders_text_blocks_into_the_backend() {
+        let backend = Rc::new(RefCell::new(MemoryBackend::new(10, 3)));
+        let (mut world, mut dispatcher) = setup(&backend);
+
+        let root = world.create_entity().with(Position::new(0, 0)).build();
+        let style = Style::new().fg(Color::Red);
+        world
+            .create_entity()
+            .with(Parent { entity: root })
+            .with(Position::new(2, 1))
+            .with(TextBlock::single_row("@").with_style(style))
+            .build();
+        run_frames(&mut world, &mut dispatcher, 2);
+
+        let backend = backend.borrow();
+        assert_eq!(
+            backend.rows(),
+            vec!["          ", "  @       ", "          "]
+        );
+        assert_eq!(backend.cell(2, 1), Some(("@".to_owned(), style)));
+        assert_eq!(backend.cell(3, 1), Some((" ".to_owned(), Style::default())));
+        assert_eq!(backend.cell(10, 1), None);
+    }
+
+    #[test]
+    fn forwards_scripted_keys() {
+        let backend = Rc::new(RefCell::new(MemoryBackend::new(10, 3)));
+        let (mut world, mut dispatcher) = setup(&backend);
+        let mut reader = world
+            .write_resource::<EventChannel<Key>>()
+            .register_reader();
+
+        backend
+            .borrow_mut()
+            .push_frame(vec![Key::Character('x'), Key::KeyPPage, Key::KeyNPage]);
+        run_frames(&mut world, &mut dispatcher, 1);
+
+        let keys: Vec<Key> = world
+            .read_resource::<EventChannel<Key>>()
+            .read(&mut reader)
+            .cloned()
+            .collect();
+        assert_eq!(
+            keys,
+            vec![Key::Character('x'), Key::KeyPPage, Key::KeyNPage]
+        );
+    }
+}
@@ -1,15 +1,12 @@
 use amethyst::ecs::prelude::*;
 
-use std::{cell::RefCell, rc::Rc};
-
 pub use super::blink::BlinkSystem;
-pub use super::{components::*, TuiChannel, TuiEvent};
+pub use super::{backend::SharedBackend, components::*, Style, TuiChannel, TuiEvent};
 pub use amethyst::core::transform::{Parent, ParentHierarchy};
 
+use super::text::{display_width, graphemes};
 use crate::specs_ext::SpecsExt;
 
-use easycurses::*;
-
 use hibitset::BitSetLike;
 
 #[derive(Default)]
@@ -19,6 +16,49 @@ impl Component for OldPosition {
     type Storage = DenseVecStorage<Self>;
 }
 
+/// A composited screen cell. `zlevel` decides which block wins a cell.
+/// A wide grapheme is stored in its first cell, followed by a cell with
+/// an empty `glyph`.
+#[derive(Clone)]
+struct Cell {
+    zlevel: i32,
+    glyph: String,
+    style: Style,
+}
+
+impl Cell {
+    fn blank() -> Self {
+        Cell {
+            zlevel: -100,
+            glyph: " ".to_owned(),
+            style: Style::default(),
+        }
+    }
+
+    fn is_continuation(&self) -> bool {
+        self.glyph.is_empty()
+    }
+
+    fn is_wide(&self) -> bool {
+        display_width(&self.glyph) > 1
+    }
+}
+
+/// Blanks out halves of wide graphemes whose other half was covered by
+/// something else.
+fn repair_wide_cells(row: &mut [Cell]) {
+    for x in 0..row.len() {
+        if row[x].is_continuation() && (x == 0 || !row[x - 1].is_wide()) {
+            row[x].glyph = " ".to_owned();
+        }
+    }
+    for x in 0..row.len() {
+        if row[x].is_wide() && row.get(x + 1).map_or(true, |next| !next.is_continuation()) {
+            row[x].glyph = " ".to_owned();
+        }
+    }
+}
+
 #[derive(Default, Copy, Clone, PartialEq)]
 pub struct ScreenSize {
     pub width: i32,
@@ -26,15 +66,15 @@ pub struct ScreenSize {
 }
 
 pub struct TuiRenderSystem {
-    easy: Rc<RefCell<EasyCurses>>,
+    backend: SharedBackend,
     tui_reader: Option<ReaderId<TuiEvent>>,
-    backplane: Vec<String>,
+    backplane: Vec<Vec<Cell>>,
 }
 
 impl TuiRenderSystem {
-    pub fn new(easy: Rc<RefCell<EasyCurses>>) -> Self {
+    pub fn new(backend: SharedBackend) -> Self {
         TuiRenderSystem {
-            easy,
+            backend,
             tui_reader: None,
             backplane: Vec::new(),
         }
@@ -58,7 +98,7 @@ impl<'s> System<'s> for TuiRenderSystem {
     type SystemData = TuiRenderSD<'s>;
 
     fn run(&mut self, mut data: Self::SystemData) {
-        let mut easy = self.easy.borrow_mut();
+        let mut backend = self.backend.borrow_mut();
 
         let mut dirty_local = BitSet::new();
 
@@ -75,12 +115,12 @@ impl<'s> System<'s> for TuiRenderSystem {
                     continue;
                 }
                 ScreenSize { new, .. } => {
-                    self.backplane = (0..new.height)
-                        .map(|_| " ".repeat(new.width as usize))
-                        .collect();
-                    for (i, row) in self.backplane.iter().enumerate() {
-                        easy.move_rc(i as i32, 0);
-                        easy.print(row);
+                    self.backplane =
+                        vec![vec![Cell::blank(); new.width as usize]; new.height as usize];
+                    backend.set_style(Style::default());
+                    for i in 0..new.height {
+                        backend.move_rc(i, 0);
+                        backend.print(&" ".repeat(new.width as usize));
                     }
                     continue;
                 }
@@ -94,14 +134,8 @@ impl<'s> System<'s> for TuiRenderSystem {
 
         let mut invisible = BitSet::new();
 
-        let mut swap: Vec<Vec<(i32, char)>> = (0..data.screen_size.height)
-            .map(|_| {
-                " ".repeat(data.screen_size.width as usize)
-                    .chars()
-                    .map(|x| (-100, x))
-                    .collect()
-            })
-            .collect();
+        let (screen_width, screen_height) = (data.screen_size.width, data.screen_size.height);
+        let mut swap = vec![vec![Cell::blank(); screen_width as usize]; screen_height as usize];
 
         for entity in data.parent_hierarchy.all() {
             if data.visible.get(*entity) == Some(&Visible(false)) {
@@ -135,78 +169,82 @@ impl<'s> System<'s> for TuiRenderSystem {
                     .iter()
                     .chain(["".to_owned()].iter().cycle())
                     .enumerate()
-                    .take((text_block.height).min(data.screen_size.height - global.0.y) as usize)
+                    .take((text_block.height).min(screen_height - global.0.y) as usize)
                 {
                     let y = i + global.0.y as usize;
-                    let extra_width = i32::max(text_block.width - row.len() as i32, 0) as usize;
-                    swap[y] = swap[y]
-                        .iter()
-                        .take(global.0.x as usize)
-                        .cloned()
-                        .chain(
-                            row.chars()
-                                .chain(" ".repeat(extra_width).chars())
-                                .map(|x| (zlevel, x))
-                                .zip(
-                                    swap[y]
-                                        .iter()
-                                        .skip(global.0.x as usize)
-                                        .take(text_block.width as usize),
-                                )
-                                .map(|(new, old)| if new.0 >= old.0 { new } else { *old }),
-                        )
-                        .chain(
-                            swap[y]
-                                .iter()
-                                .skip((global.0.x + text_block.width) as usize)
-                                .cloned(),
-                        )
-                        .take(swap[y].len())
-                        .collect();
+                    let mut put = |column: i32, glyph: &str| {
+                        let x = global.0.x + column;
+                        if x < 0 || x >= screen_width {
+                            return;
+                        }
+                        let cell = &mut swap[y][x as usize];
+                        if zlevel >= cell.zlevel {
+                            *cell = Cell {
+                                zlevel,
+                                glyph: glyph.to_owned(),
+                                style: text_block.style_at(column as usize, i),
+                            };
+                        }
+                    };
+
+                    let mut column = 0;
+                    for (grapheme, width) in graphemes(row) {
+                        if column + width as i32 > text_block.width {
+                            break;
+                        }
+                        put(column, grapheme);
+                        for extra in 1..width as i32 {
+                            put(column + extra, "");
+                        }
+                        column += width as i32;
+                    }
+                    for column in column..text_block.width {
+                        put(column, " ");
+                    }
                 }
             }
         }
 
-        for y in 0..data.screen_size.height as usize {
+        for row in &mut swap {
+            repair_wide_cells(row);
+        }
+
+        let mut style = None;
+        for y in 0..screen_height as usize {
             let mut skipped = true;
-            for (x, (old, new)) in self.backplane[y]
-                .chars()
-                .zip(swap[y].iter().map(|x| x.1))
-                .enumerate()
-            {
-                if old != new {
+            for (x, (old, new)) in self.backplane[y].iter().zip(swap[y].iter()).enumerate() {
+                // Printed together with the first half.
+                if new.is_continuation() {
+                    continue;
+                }
+                if (&old.glyph, old.style) != (&new.glyph, new.style) {
                     if skipped {
-                        easy.move_rc(y as i32, x as i32);
+                        backend.move_rc(y as i32, x as i32);
                         skipped = false;
                     }
-                    easy.print_char(new);
+                    if style != Some(new.style) {
+                        backend.set_style(new.style);
+                        style = Some(new.style);
+                    }
+                    backend.print(&new.glyph);
                 } else {
                     skipped = true;
                 }
             }
         }
 
-        self.backplane = swap
-            .into_iter()
-            .map(|x| x.into_iter().map(|x| x.1).collect())
-            .collect();
+        self.backplane = swap;
 
-        easy.refresh();
+        backend.refresh();
     }
 
     fn setup(&mut self, res: &mut Resources) {
         Self::SystemData::setup(res);
 
-        let mut easy = self.easy.borrow_mut();
-        easy.set_cursor_visibility(CursorVisibility::Invisible);
-        easy.set_echo(false);
-        easy.set_keypad_enabled(true);
-        easy.set_input_mode(InputMode::Character);
-        easy.set_input_timeout(TimeoutMode::Immediate);
-        easy.set_scrolling(true);
-        easy.set_color_pair(ColorPair::new(Color::White, Color::Black));
+        let mut backend = self.backend.borrow_mut();
+        backend.init();
 
-        let (height, width) = easy.get_row_col_count();
+        let (width, height) = backend.size();
         res.get_mut::<crate::resources::LogEvents>()
             .unwrap()
             .send(format!("({}, {})", height, width));
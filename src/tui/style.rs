@@ -0,0 +1,66 @@
+/// The eight basic terminal colors.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Color {
+    Black,
+    Red,
+    Green,
+    Yellow,
+    Blue,
+    Magenta,
+    Cyan,
+    White,
+}
+
+/// How a single cell is drawn.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct Style {
+    pub fg: Color,
+    pub bg: Color,
+    pub bold: bool,
+    pub reverse: bool,
+    pub dim: bool,
+}
+
+impl Default for Style {
+    fn default() -> Self {
+        Style {
+            fg: Color::White,
+            bg: Color::Black,
+            bold: false,
+            reverse: false,
+            dim: false,
+        }
+    }
+}
+
+#[allow(dead_code)]
+impl Style {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    pub fn fg(mut self, color: Color) -> Self {
+        self.fg = color;
+        self
+    }
+
+    pub fn bg(mut self, color: Color) -> Self {
+        self.bg = color;
+        self
+    }
+
+    pub fn bold(mut self) -> Self {
+        self.bold = true;
+        self
+    }
+
+    pub fn reverse(mut self) -> Self {
+        self.reverse = true;
+        self
+    }
+
+    pub fn dim(mut self) -> Self {
+        self.dim = true;
+        self
+    }
+}
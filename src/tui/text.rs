@@ -0,0 +1,34 @@
+use std::iter;
+
+use unicode_segmentation::UnicodeSegmentation;
+use unicode_width::UnicodeWidthStr;
+
+/// Splits `text` into grapheme clusters, each paired with the number of
+/// terminal columns it takes up. Clusters that take up no columns, like
+/// control characters, are dropped.
+pub fn graphemes(text: &str) -> impl Iterator<Item = (&str, usize)> {
+    text.graphemes(true)
+        .map(|grapheme| (grapheme, grapheme.width().min(2)))
+        .filter(|&(_, width)| width > 0)
+}
+
+/// The number of terminal columns `text` takes up.
+pub fn display_width(text: &str) -> usize {
+    graphemes(text).map(|(_, width)| width).sum()
+}
+
+/// Cuts `text` down to at most `width` columns, then pads it with spaces
+/// to exactly `width` columns.
+pub fn fit_width(text: &str, width: usize) -> String {
+    let mut fitted = String::new();
+    let mut used = 0;
+    for (grapheme, grapheme_width) in graphemes(text) {
+        if used + grapheme_width > width {
+            break;
+        }
+        fitted.push_str(grapheme);
+        used += grapheme_width;
+    }
+    fitted.extend(iter::repeat(' ').take(width - used));
+    fitted
+}
@@ -0,0 +1,135 @@
+use super::{text::fit_width, Style, TextBlock};
+use amethyst::ecs::prelude::*;
+
+const SCROLLBAR_TRACK: char = '│';
+const SCROLLBAR_THUMB: char = '█';
+
+/// Shows a window of `rows` in the entity's `TextBlock`, with a scrollbar
+/// in the last column when they don't all fit.
+#[derive(Default, Debug, Clone)]
+pub struct Viewport {
+    pub rows: Vec<String>,
+    /// Per-cell styles of `rows`, like `TextBlock::styles`.
+    pub styles: Vec<Vec<Style>>,
+    /// Rows at the top that stay put while the rest scrolls, e.g. a title.
+    pub header: usize,
+    /// The first visible row below the header.
+    pub offset: usize,
+    /// A row below the header, e.g. a cursor, that is kept in view.
+    pub follow: Option<usize>,
+    /// Columns available to rows, the last one is left for the scrollbar.
+    /// Kept up to date by `ViewportSystem`.
+    pub width: usize,
+    /// Number of visible rows below the header. Kept up to date by
+    /// `ViewportSystem`.
+    pub page: usize,
+}
+
+impl Viewport {
+    pub fn new(header: usize) -> Self {
+        Viewport {
+            header,
+            ..Viewport::default()
+        }
+    }
+
+    /// Scrolls by `rows`, negative is up. Scrolling too far down is undone
+    /// by `ViewportSystem`.
+    pub fn scroll_by(&mut self, rows: i32) {
+        self.offset = (self.offset as i32 + rows).max(0) as usize;
+    }
+
+    pub fn page_up(&mut self) {
+        self.scroll_by(-(self.page as i32));
+    }
+
+    pub fn page_down(&mut self) {
+        self.scroll_by(self.page as i32);
+    }
+
+    /// Keeps `follow` in view and the offset within the rows.
+    fn clamp(&mut self) {
+        let body = self.rows.len().saturating_sub(self.header);
+        if let Some(row) = self.follow {
+            let row = row.min(body.saturating_sub(1));
+            if row < self.offset {
+                self.offset = row;
+            } else if row >= self.offset + self.page {
+                self.offset = row + 1 - self.page.min(row + 1);
+            }
+        }
+        self.offset = self.offset.min(body.saturating_sub(self.page));
+    }
+
+    /// The glyph in the scrollbar column at `row` below the header, if the
+    /// rows don't all fit.
+    fn scrollbar(&self, row: usize) -> Option<char> {
+        let body = self.rows.len().saturating_sub(self.header);
+        if body <= self.page || self.page == 0 {
+            return None;
+        }
+        let thumb = (self.page * self.page / body).max(1);
+        let start = self.offset * (self.page - thumb) / (body - self.page);
+        if row >= start && row < start + thumb {
+            Some(SCROLLBAR_THUMB)
+        } else {
+            Some(SCROLLBAR_TRACK)
+        }
+    }
+}
+
+impl Component for Viewport {
+    type Storage = DenseVecStorage<Self>;
+}
+
+#[derive(Default)]
+pub struct ViewportSystem;
+
+#[derive(SystemData)]
+pub struct SystemData<'s> {
+    viewport: WriteStorage<'s, Viewport>,
+    text_block: WriteStorage<'s, TextBlock>,
+    entities: Entities<'s>,
+}
+
+impl<'s> System<'s> for ViewportSystem {
+    type SystemData = SystemData<'s>;
+
+    fn run(&mut self, mut data: Self::SystemData) {
+        for (entity, viewport) in (&data.entities, &mut data.viewport).join() {
+            let (width, height) = match data.text_block.get(entity) {
+                Some(block) => (block.width.max(0) as usize, block.height.max(0) as usize),
+                None => continue,
+            };
+            viewport.width = width.saturating_sub(1);
+            viewport.page = height.saturating_sub(viewport.header);
+            viewport.clamp();
+
+            let visible = (0..viewport.header.min(viewport.rows.len())).chain(
+                (viewport.header + viewport.offset..viewport.rows.len()).take(viewport.page),
+            );
+            let mut rows = Vec::new();
+            let mut styles = Vec::new();
+            for (screen_row, row) in visible.enumerate() {
+                let mut text = fit_width(&viewport.rows[row], viewport.width);
+                if let Some(glyph) = screen_row
+                    .checked_sub(viewport.header)
+                    .and_then(|x| viewport.scrollbar(x))
+                {
+                    text.push(glyph);
+                }
+                rows.push(text);
+                styles.push(viewport.styles.get(row).cloned().unwrap_or_default());
+            }
+
+            // Only touch the block when something changed, so that it isn't
+            // flagged as modified every frame.
+            let block = data.text_block.get(entity).unwrap();
+            if block.rows != rows || block.styles != styles {
+                let block = data.text_block.get_mut(entity).unwrap();
+                block.rows = rows;
+                block.styles = styles;
+            }
+        }
+    }
+}